@@ -0,0 +1,353 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{Candle, CandleAggregator, TimeFrame};
+use crate::signal::TimeFrameData;
+
+
+#[derive(Debug, Error)]
+pub enum ImportError{
+    #[error("io: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("csv: {0}")]
+    Csv(#[from] csv::Error),
+    #[cfg(feature = "parquet")]
+    #[error("parquet: {0}")]
+    Parquet(#[from] parquet::errors::ParquetError),
+    #[error("missing column {0}")]
+    MissingColumn(String),
+    #[error("row {row}: bad {column} value {value:?}")]
+    BadValue{row: usize, column: String, value: String},
+    #[error("row {row}: open time {time} is before the previous row ({prev})")]
+    Unordered{row: usize, prev: u64, time: u64},
+    #[error("no candles in file")]
+    Empty,
+    #[error(".parquet files need the `parquet` feature")]
+    ParquetDisabled,
+    #[error("{} bars can't be resampled to {}, the target has to be a whole multiple", from.as_str(), to.as_str())]
+    Resample{from: TimeFrame, to: TimeFrame},
+}
+
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TimestampFormat{
+    UnixMillis,
+    UnixSeconds,
+    Rfc3339,
+    //chrono format string, read as UTC, e.g. "%Y-%m-%d %H:%M:%S"
+    Custom(String),
+}
+
+impl TimestampFormat{
+    fn parse(&self, value: &str) -> Option<u64>{
+        let value = value.trim();
+        match self{
+            TimestampFormat::UnixMillis => value.parse::<f64>().ok().map(|t| t as u64),
+            TimestampFormat::UnixSeconds => value.parse::<f64>().ok().map(|t| (t * 1000.0) as u64),
+            TimestampFormat::Rfc3339 => DateTime::parse_from_rfc3339(value).ok()
+                .map(|t| t.timestamp_millis() as u64),
+            TimestampFormat::Custom(fmt) => NaiveDateTime::parse_from_str(value, fmt)
+                .or_else(|_| NaiveDate::parse_from_str(value, fmt).map(|d| d.and_hms_opt(0, 0, 0).unwrap()))
+                .ok()
+                .map(|t| t.and_utc().timestamp_millis() as u64),
+        }
+    }
+}
+
+
+//Names of the source columns, volume and trades are optional: None or "" (TOML has no null) skips them
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ColumnMap{
+    pub time: String,
+    pub open: String,
+    pub high: String,
+    pub low: String,
+    pub close: String,
+    pub volume: Option<String>,
+    pub trades: Option<String>,
+}
+
+impl Default for ColumnMap{
+    fn default() -> Self{
+        Self{
+            time: "time".to_string(),
+            open: "open".to_string(),
+            high: "high".to_string(),
+            low: "low".to_string(),
+            close: "close".to_string(),
+            volume: Some("volume".to_string()),
+            trades: None,
+        }
+    }
+}
+
+impl ColumnMap{
+    fn optional(name: &Option<String>) -> Option<&str>{
+        name.as_deref().filter(|n| !n.is_empty())
+    }
+
+    pub fn volume(&self) -> Option<&str>{
+        Self::optional(&self.volume)
+    }
+
+    pub fn trades(&self) -> Option<&str>{
+        Self::optional(&self.trades)
+    }
+}
+
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportConfig{
+    //timeframe of the rows in the file
+    pub tf: TimeFrame,
+    #[serde(default)]
+    pub columns: ColumnMap,
+    #[serde(default = "default_timestamp")]
+    pub timestamp: TimestampFormat,
+    #[serde(default = "default_delimiter")]
+    pub delimiter: char,
+}
+
+fn default_timestamp() -> TimestampFormat{
+    TimestampFormat::UnixMillis
+}
+
+fn default_delimiter() -> char{
+    ','
+}
+
+impl ImportConfig{
+    pub fn new(tf: TimeFrame) -> Self{
+        Self{
+            tf,
+            columns: ColumnMap::default(),
+            timestamp: default_timestamp(),
+            delimiter: default_delimiter(),
+        }
+    }
+}
+
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ValidationReport{
+    pub rows: usize,
+    pub duplicates: usize,
+    //open times not on a bar boundary of the timeframe
+    pub misaligned: usize,
+    //[from, to) ranges with no bars
+    pub gaps: Vec<(u64, u64)>,
+}
+
+
+#[derive(Debug, Clone)]
+pub struct CandleSeries{
+    pub tf: TimeFrame,
+    pub candles: Vec<Candle>,
+    pub report: ValidationReport,
+}
+
+impl CandleSeries{
+
+    pub fn new(tf: TimeFrame, candles: Vec<Candle>) -> Result<Self, ImportError>{
+        let (candles, report) = validate(tf, candles)?;
+        if report.duplicates > 0 || !report.gaps.is_empty() || report.misaligned > 0{
+            warn!("{} series: {} duplicate(s), {} gap(s), {} misaligned bar(s)",
+                tf.as_str(), report.duplicates, report.gaps.len(), report.misaligned);
+        }
+        Ok(Self{ tf, candles, report })
+    }
+
+    //only whole multiples of the series' timeframe can be built from it,
+    //weeks and months don't line up with bars longer than a day
+    pub fn check_resample(&self, tf: TimeFrame) -> Result<(), ImportError>{
+        let (from, to) = (self.tf.to_millis(), tf.to_millis());
+        let calendar = matches!(tf, TimeFrame::Week | TimeFrame::Month) && from > TimeFrame::Day1.to_millis();
+        if to < from || to % from != 0 || (calendar && tf != self.tf){
+            return Err(ImportError::Resample{from: self.tf, to: tf});
+        }
+        Ok(())
+    }
+
+    //bars of a coarser timeframe built from this series
+    pub fn resample(&self, tf: TimeFrame) -> Result<Vec<Candle>, ImportError>{
+        self.check_resample(tf)?;
+        if tf == self.tf{
+            return Ok(self.candles.clone());
+        }
+        let mut aggregator = CandleAggregator::new(tf);
+        let mut bars: Vec<Candle> = Vec::new();
+        for candle in &self.candles{
+            let bar = aggregator.update(*candle);
+            match bars.last_mut(){
+                Some(last) if last.open_time == bar.open_time => *last = bar,
+                _ => bars.push(bar),
+            }
+        }
+        Ok(bars)
+    }
+
+    //one series per timeframe, ready for SignalEngine::load
+    pub fn by_timeframe<I: IntoIterator<Item = TimeFrame>>(&self, tfs: I) -> Result<TimeFrameData, ImportError>{
+        let mut data: TimeFrameData = HashMap::new();
+        for tf in tfs{
+            if !data.contains_key(&tf){
+                data.insert(tf, self.resample(tf)?);
+            }
+        }
+        Ok(data)
+    }
+}
+
+
+fn validate(tf: TimeFrame, candles: Vec<Candle>) -> Result<(Vec<Candle>, ValidationReport), ImportError>{
+    if candles.is_empty(){
+        return Err(ImportError::Empty);
+    }
+    let mut report = ValidationReport{ rows: candles.len(), ..Default::default() };
+    let mut out: Vec<Candle> = Vec::with_capacity(candles.len());
+
+    for (row, candle) in candles.into_iter().enumerate(){
+        if tf.bar_open(candle.open_time) != candle.open_time{
+            report.misaligned += 1;
+        }
+        if let Some(prev) = out.last_mut(){
+            if candle.open_time < prev.open_time{
+                return Err(ImportError::Unordered{row, prev: prev.open_time, time: candle.open_time});
+            }
+            if candle.open_time == prev.open_time{
+                report.duplicates += 1;
+                *prev = candle;
+                continue;
+            }
+            let next = tf.next_open(prev.open_time);
+            if candle.open_time > next{
+                report.gaps.push((next, candle.open_time));
+            }
+        }
+        out.push(candle);
+    }
+    Ok((out, report))
+}
+
+
+struct RowReader<'a>{
+    cfg: &'a ImportConfig,
+    row: usize,
+}
+
+impl RowReader<'_>{
+    fn number(&self, column: &str, value: &str) -> Result<f64, ImportError>{
+        value.trim().parse::<f64>().map_err(|_| self.bad(column, value))
+    }
+
+    fn time(&self, value: &str) -> Result<u64, ImportError>{
+        self.cfg.timestamp.parse(value).ok_or_else(|| self.bad(&self.cfg.columns.time, value))
+    }
+
+    fn bad(&self, column: &str, value: &str) -> ImportError{
+        ImportError::BadValue{row: self.row, column: column.to_string(), value: value.to_string()}
+    }
+}
+
+
+pub fn read_csv<P: AsRef<Path>>(path: P, cfg: &ImportConfig) -> Result<CandleSeries, ImportError>{
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(cfg.delimiter as u8)
+        .trim(csv::Trim::All)
+        .from_path(path)?;
+
+    let headers = reader.headers()?.clone();
+    let index = |name: &str| headers.iter().position(|h| h == name)
+        .ok_or_else(|| ImportError::MissingColumn(name.to_string()));
+    let cols = &cfg.columns;
+    let (t, o, h, l, c) = (index(&cols.time)?, index(&cols.open)?, index(&cols.high)?, index(&cols.low)?, index(&cols.close)?);
+    let v = cols.volume().map(index).transpose()?;
+    let n = cols.trades().map(index).transpose()?;
+
+    let mut candles = Vec::new();
+    for (row, record) in reader.records().enumerate(){
+        let record = record?;
+        let rr = RowReader{ cfg, row };
+        let field = |i: usize| record.get(i).unwrap_or("");
+        candles.push(Candle{
+            open_time: rr.time(field(t))?,
+            open: rr.number(&cols.open, field(o))?,
+            high: rr.number(&cols.high, field(h))?,
+            low: rr.number(&cols.low, field(l))?,
+            close: rr.number(&cols.close, field(c))?,
+            volume: match v{ Some(i) => rr.number("volume", field(i))?, None => 0.0 },
+            trades: match n{ Some(i) => rr.number("trades", field(i))? as u64, None => 0 },
+        });
+    }
+    CandleSeries::new(cfg.tf, candles)
+}
+
+
+#[cfg(feature = "parquet")]
+pub fn read_parquet<P: AsRef<Path>>(path: P, cfg: &ImportConfig) -> Result<CandleSeries, ImportError>{
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+    use parquet::record::Field;
+
+    let file = std::fs::File::open(path)?;
+    let reader = SerializedFileReader::new(file)?;
+    let cols = &cfg.columns;
+
+    let mut candles = Vec::new();
+    for (row, record) in reader.get_row_iter(None)?.enumerate(){
+        let record = record?;
+        let rr = RowReader{ cfg, row };
+        let fields: HashMap<&str, &Field> = record.get_column_iter().map(|(name, f)| (name.as_str(), f)).collect();
+        let get = |name: &str| fields.get(name).copied().ok_or_else(|| ImportError::MissingColumn(name.to_string()));
+
+        let number = |name: &str| -> Result<f64, ImportError>{
+            match get(name)?{
+                Field::Double(x) => Ok(*x),
+                Field::Float(x) => Ok(*x as f64),
+                Field::Long(x) => Ok(*x as f64),
+                Field::Int(x) => Ok(*x as f64),
+                Field::ULong(x) => Ok(*x as f64),
+                Field::UInt(x) => Ok(*x as f64),
+                Field::Str(s) => rr.number(name, s),
+                other => Err(rr.bad(name, &other.to_string())),
+            }
+        };
+        let time = match get(&cols.time)?{
+            Field::TimestampMillis(t) => *t as u64,
+            Field::TimestampMicros(t) => (*t / 1000) as u64,
+            Field::Str(s) => rr.time(s)?,
+            other => rr.time(&other.to_string())?,
+        };
+
+        candles.push(Candle{
+            open_time: time,
+            open: number(&cols.open)?,
+            high: number(&cols.high)?,
+            low: number(&cols.low)?,
+            close: number(&cols.close)?,
+            volume: match cols.volume(){ Some(name) => number(name)?, None => 0.0 },
+            trades: match cols.trades(){ Some(name) => number(name)? as u64, None => 0 },
+        });
+    }
+    CandleSeries::new(cfg.tf, candles)
+}
+
+
+//picks the reader from the file extension
+pub fn read_candles<P: AsRef<Path>>(path: P, cfg: &ImportConfig) -> Result<CandleSeries, ImportError>{
+    match path.as_ref().extension().and_then(|e| e.to_str()){
+        #[cfg(feature = "parquet")]
+        Some("parquet") => read_parquet(path, cfg),
+        #[cfg(not(feature = "parquet"))]
+        Some("parquet") => Err(ImportError::ParquetDisabled),
+        _ => read_csv(path, cfg),
+    }
+}
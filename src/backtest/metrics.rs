@@ -0,0 +1,144 @@
+use serde::{Deserialize, Serialize};
+
+use super::BacktestResult;
+
+
+pub const YEAR_MS: f64 = 365.0 * 24.0 * 60.0 * 60.0 * 1000.0;
+
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Metrics{
+    pub trades: usize,
+    pub total_pnl: f64,
+    pub total_fees: f64,
+    pub return_pct: f64,
+    pub win_rate: f64,
+    pub profit_factor: f64,
+    //annualized, from per-bar equity returns
+    pub sharpe: f64,
+    //largest peak to trough drop of equity, as a fraction of the peak
+    pub max_drawdown: f64,
+}
+
+impl Metrics{
+
+    pub fn new(result: &BacktestResult) -> Self{
+        let trades = result.trades.len();
+        let total_pnl: f64 = result.trades.iter().map(|t| t.pnl).sum();
+        let total_fees: f64 = result.trades.iter().map(|t| t.fee).sum();
+        let wins = result.trades.iter().filter(|t| t.pnl > 0.0).count();
+        let gross_win: f64 = result.trades.iter().filter(|t| t.pnl > 0.0).map(|t| t.pnl).sum();
+        let gross_loss: f64 = result.trades.iter().filter(|t| t.pnl < 0.0).map(|t| -t.pnl).sum();
+
+        let profit_factor = if gross_loss > 0.0{
+            gross_win / gross_loss
+        }else if gross_win > 0.0{
+            f64::INFINITY
+        }else{
+            0.0
+        };
+
+        Self{
+            trades,
+            total_pnl,
+            total_fees,
+            return_pct: if result.start_margin > 0.0 {100.0 * total_pnl / result.start_margin} else {0.0},
+            win_rate: if trades > 0 {wins as f64 / trades as f64} else {0.0},
+            profit_factor,
+            sharpe: sharpe(&result.equity, result.tf.to_millis()),
+            max_drawdown: max_drawdown(result.equity.iter().map(|e| e.1)),
+        }
+    }
+}
+
+
+fn returns(equity: &[(u64, f64)]) -> Vec<f64>{
+    equity.windows(2)
+        .filter(|w| w[0].1 > 0.0)
+        .map(|w| w[1].1 / w[0].1 - 1.0)
+        .collect()
+}
+
+pub fn sharpe(equity: &[(u64, f64)], bar_ms: u64) -> f64{
+    let returns = returns(equity);
+    if returns.len() < 2{
+        return 0.0;
+    }
+    let n = returns.len() as f64;
+    let mean = returns.iter().sum::<f64>() / n;
+    let var = returns.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / (n - 1.0);
+    if var == 0.0{
+        return 0.0;
+    }
+    mean / var.sqrt() * (YEAR_MS / bar_ms as f64).sqrt()
+}
+
+//like sharpe but only penalizes downside deviation
+pub fn sortino(equity: &[(u64, f64)], bar_ms: u64) -> f64{
+    let returns = returns(equity);
+    if returns.len() < 2{
+        return 0.0;
+    }
+    let n = returns.len() as f64;
+    let mean = returns.iter().sum::<f64>() / n;
+    let downside = returns.iter().map(|r| r.min(0.0).powi(2)).sum::<f64>() / n;
+    if downside == 0.0{
+        return 0.0;
+    }
+    mean / downside.sqrt() * (YEAR_MS / bar_ms as f64).sqrt()
+}
+
+//longest time equity spent below a previous peak, in ms
+pub fn max_drawdown_duration(equity: &[(u64, f64)]) -> u64{
+    let mut peak = f64::MIN;
+    let mut peak_time = 0;
+    let mut longest = 0;
+    for &(time, value) in equity{
+        if value >= peak{
+            peak = value;
+            peak_time = time;
+        }else{
+            longest = longest.max(time.saturating_sub(peak_time));
+        }
+    }
+    longest
+}
+
+pub fn max_drawdown<I: IntoIterator<Item = f64>>(equity: I) -> f64{
+    let mut peak = f64::MIN;
+    let mut worst = 0.0_f64;
+    for value in equity{
+        peak = peak.max(value);
+        if peak > 0.0{
+            worst = worst.max((peak - value) / peak);
+        }
+    }
+    worst
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Objective{
+    Sharpe,
+    ProfitFactor,
+    //return divided by max drawdown
+    DrawdownAdjusted,
+    TotalPnl,
+}
+
+impl Objective{
+    //higher is better, configs without trades rank last
+    pub fn score(&self, m: &Metrics) -> f64{
+        if m.trades == 0{
+            return f64::MIN;
+        }
+        match self{
+            Objective::Sharpe => m.sharpe,
+            Objective::ProfitFactor => m.profit_factor.min(1e6),
+            Objective::DrawdownAdjusted => m.return_pct / (100.0 * m.max_drawdown).max(1.0),
+            Objective::TotalPnl => m.total_pnl,
+        }
+    }
+}
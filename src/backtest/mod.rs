@@ -0,0 +1,363 @@
+mod import;
+mod metrics;
+mod optimize;
+mod walk_forward;
+mod monte_carlo;
+mod report;
+mod portfolio;
+
+pub use import::{
+    CandleSeries,
+    ColumnMap,
+    ImportConfig,
+    ImportError,
+    TimestampFormat,
+    ValidationReport,
+    read_candles,
+    read_csv,
+};
+#[cfg(feature = "parquet")]
+pub use import::read_parquet;
+
+pub use metrics::{Metrics, Objective};
+pub use optimize::{
+    IndicatorRange,
+    ParamSpace,
+    Candidate,
+    Search,
+    Optimizer,
+    OptResult,
+    export_top,
+};
+pub use walk_forward::{
+    WalkForwardConfig,
+    WalkForwardReport,
+    WindowResult,
+    ParamStability,
+};
+pub use monte_carlo::{
+    MonteCarloConfig,
+    MonteCarloReport,
+    Distribution,
+    Resampling,
+    Sizing,
+    monte_carlo,
+};
+pub use report::{Report, SideStats, REPORT_DIR};
+pub use portfolio::{Portfolio, PortfolioResult, MarketResult, SplitResult};
+
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{SignalEngine, MARKETS, IndexId};  
+use crate::{Candle, FundingSchedule, MarginTable};
+use crate::signal::ExecParam;
+use crate::trade_setup::{TradeParams, TimeFrame, TradeCommand, TradeInfo, PositionSnapshot, LiquidationFillInfo};
+
+
+//Fill model, fee is the taker rate and slippage a fraction of price paid on every fill
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SimParams{
+    pub fee: f64,
+    pub slippage: f64,
+}
+
+impl Default for SimParams{
+    fn default() -> Self{
+        Self{
+            fee: 0.00045,
+            slippage: 0.0,
+        }
+    }
+}
+
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BacktestResult{
+    pub start_margin: f64,
+    pub end_margin: f64,
+    pub tf: TimeFrame,
+    pub trades: Vec<TradeInfo>,
+    //(time, margin + unrealized pnl) at every bar
+    pub equity: Vec<(u64, f64)>,
+    pub liquidations: Vec<LiquidationFillInfo>,
+}
+
+
+impl BacktestResult{
+    pub fn monte_carlo(&self, sizing: Sizing, cfg: MonteCarloConfig) -> MonteCarloReport{
+        monte_carlo(&self.trades, self.start_margin, sizing, cfg)
+    }
+
+    pub fn report(&self, title: &str) -> Report{
+        Report::from_backtest(title, self)
+    }
+}
+
+
+#[derive(Debug, Clone, Copy)]
+struct SimPosition{
+    is_long: bool,
+    size: f64,
+    entry: f64,
+    open_time: u64,
+    close_at: Option<u64>,
+    oid: u64,
+    //isolated margin backing the position
+    margin: f64,
+    //accrued since open, realized with the close
+    funding: f64,
+}
+
+
+//State of a run in progress
+#[derive(Debug, Default)]
+struct RunState{
+    start_margin: Option<f64>,
+    position: Option<SimPosition>,
+    trades: Vec<TradeInfo>,
+    equity: Vec<(u64, f64)>,
+    liquidations: Vec<LiquidationFillInfo>,
+    oid: u64,
+    //(time, close) of the last bar played
+    last: Option<(u64, f64)>,
+}
+
+
+pub struct BackTester{
+    pub asset: String,
+    pub signal_engine: SignalEngine,
+    pub params: TradeParams,
+    pub candle_data: Vec<Candle>,
+    pub margin: f64,
+    pub sim: SimParams,
+    //empty schedule = no funding
+    pub funding: FundingSchedule,
+    //defaults to the traded leverage being the asset's max, the earliest liquidation it can have
+    pub margin_table: MarginTable,
+    //timeframe of candle_data
+    data_tf: TimeFrame,
+    state: RunState,
+}
+
+
+
+
+impl BackTester{
+
+    //`max_lev` is the asset's max leverage, set its real tiers on `margin_table` for large notionals
+    pub fn new(asset: &str,params: TradeParams, config: Option<Vec<IndexId>>, margin: f64, max_lev: u32) -> Self{
+        if !MARKETS.contains(&asset){
+            panic!("ASSET ISN'T TRADABLE, MARKET CAN'T BE INITILIAZED");
+        }
+
+        let margin_table = MarginTable::flat(max_lev);
+        BackTester{
+            asset: asset.to_string(),
+            signal_engine: SignalEngine::new_backtest(params.clone(), config, margin),
+            data_tf: params.time_frame,
+            params,
+            candle_data: Vec::new(),
+            margin,
+            sim: SimParams::default(),
+            funding: FundingSchedule::default(),
+            margin_table,
+            state: RunState::default(),
+        }
+    }
+
+    //first `warm_up` bars of the series load the indicators, the rest is kept to be played
+    pub async fn load_series(&mut self, series: &CandleSeries, warm_up: usize) -> Result<(), ImportError>{
+        let warm_up = warm_up.min(series.candles.len());
+        let cutoff = series.candles.get(warm_up).map_or(u64::MAX, |c| c.open_time);
+
+        let mut tfs: HashSet<TimeFrame> = self.signal_engine.get_active_indicators().into_iter().map(|id| id.1).collect();
+        tfs.insert(self.params.time_frame);
+
+        for (tf, bars) in series.by_timeframe(tfs)?{
+            let closed: Vec<Candle> = bars.into_iter().filter(|c| tf.next_open(c.open_time) <= cutoff).collect();
+            self.signal_engine.load(tf, closed).await;
+        }
+        self.candle_data = series.candles[warm_up..].to_vec();
+        self.data_tf = series.tf;
+        Ok(())
+    }
+
+
+    //plays candle_data through the engine, orders fill at the close of the bar that produced them
+    pub fn run(&mut self) -> BacktestResult{
+        let candles = std::mem::take(&mut self.candle_data);
+        self.state.equity.reserve(candles.len());
+        for candle in &candles{
+            self.play(candle);
+        }
+        self.finish()
+    }
+
+    //plays a single bar, returns the trades it closed
+    pub(crate) fn play(&mut self, candle: &Candle) -> &[TradeInfo]{
+        let mut run = std::mem::take(&mut self.state);
+        run.start_margin.get_or_insert(self.margin);
+        let before = run.trades.len();
+        let time = self.data_tf.next_open(candle.open_time);
+
+        if let Some(pos) = run.position.as_mut(){
+            pos.funding += self.funding.payment(pos.is_long, pos.size, candle.close, candle.open_time, time);
+        }
+
+        if let Some(pos) = run.position{
+            let liq = self.margin_table.liquidation_price(pos.is_long, pos.size, pos.entry, pos.margin + pos.funding);
+            if (pos.is_long && candle.low <= liq) || (!pos.is_long && candle.high >= liq){
+                run.position = None;
+                run.liquidations.push(self.liquidate(pos, liq, time, &mut run.trades));
+            }
+        }
+
+        if let Some(pos) = run.position{
+            if pos.close_at.is_some_and(|at| at <= time){
+                run.position = self.close(pos, pos.size, candle.close, time, &mut run.trades);
+            }
+        }
+
+        match self.signal_engine.step(*candle, time){
+            Some(TradeCommand::ExecuteTrade{size, is_long, duration}) if run.position.is_none() => {
+                run.position = self.open(size, is_long, candle.close, time, Some(time + duration * 1000), &mut run.oid);
+            },
+            Some(TradeCommand::OpenTrade{size, is_long}) | Some(TradeCommand::BuildPosition{size, is_long, ..}) if run.position.is_none() => {
+                run.position = self.open(size, is_long, candle.close, time, None, &mut run.oid);
+            },
+            Some(TradeCommand::CloseTrade{size}) => {
+                if let Some(pos) = run.position{
+                    run.position = self.close(pos, size.min(pos.size), candle.close, time, &mut run.trades);
+                }
+            },
+            _ => {},
+        }
+
+        let upnl = run.position.map_or(0.0, |p| Self::upnl(&p, candle.close) + p.funding);
+        run.equity.push((time, self.margin + upnl));
+        run.last = Some((time, candle.close));
+
+        self.state = run;
+        &self.state.trades[before..]
+    }
+
+    //margin + unrealized pnl after the last bar played
+    pub(crate) fn equity(&self) -> f64{
+        self.state.equity.last().map_or(self.margin, |e| e.1)
+    }
+
+    //closes what is still open at the last price and hands over the run
+    pub(crate) fn finish(&mut self) -> BacktestResult{
+        let mut run = std::mem::take(&mut self.state);
+        if let (Some(pos), Some((time, price))) = (run.position, run.last){
+            self.close(pos, pos.size, price, time, &mut run.trades);
+        }
+
+        BacktestResult{
+            start_margin: run.start_margin.unwrap_or(self.margin),
+            end_margin: self.margin,
+            tf: self.data_tf,
+            trades: run.trades,
+            equity: run.equity,
+            liquidations: run.liquidations,
+        }
+    }
+
+
+    fn open(&mut self, size: f64, is_long: bool, price: f64, time: u64, close_at: Option<u64>, oid: &mut u64) -> Option<SimPosition>{
+        let entry = if is_long {price * (1.0 + self.sim.slippage)} else {price * (1.0 - self.sim.slippage)};
+        let lev = self.params.lev.min(self.margin_table.max_lev(size * entry)).max(1);
+        let max_size = self.margin * lev as f64 / entry;
+        let size = size.min(max_size);
+        if size <= 0.0{
+            return None;
+        }
+        *oid += 2;
+        let margin = size * entry / lev as f64;
+        let pos = SimPosition{ is_long, size, entry, open_time: time, close_at, oid: *oid, margin, funding: 0.0 };
+        self.signal_engine.update_position(Some(Self::snapshot(&pos)));
+        Some(pos)
+    }
+
+    //closes `size` of the position, returns what is left of it
+    fn close(&mut self, pos: SimPosition, size: f64, price: f64, time: u64, trades: &mut Vec<TradeInfo>) -> Option<SimPosition>{
+        let exit = if pos.is_long {price * (1.0 - self.sim.slippage)} else {price * (1.0 + self.sim.slippage)};
+        let fee = size * (pos.entry + exit) * self.sim.fee;
+        let gross = if pos.is_long {size * (exit - pos.entry)} else {size * (pos.entry - exit)};
+        let funding = pos.funding * size / pos.size;
+
+        let trade = TradeInfo{
+            open: pos.entry,
+            close: exit,
+            pnl: gross - fee + funding,
+            fee,
+            funding,
+            is_long: pos.is_long,
+            duration: Some((time - pos.open_time) / 1000),
+            oid: (pos.oid, pos.oid + 1),
+            regime: self.signal_engine.get_regime(),
+        };
+        self.margin += trade.pnl;
+        self.signal_engine.on_trade_closed(&trade);
+        self.signal_engine.update_exec_params(ExecParam::Margin(self.margin));
+        trades.push(trade);
+
+        let rest = Some(SimPosition{
+            size: pos.size - size,
+            margin: pos.margin * (1.0 - size / pos.size),
+            funding: pos.funding - funding,
+            ..pos
+        }).filter(|p| p.size > 0.0);
+        self.signal_engine.update_position(rest.map(|p| Self::snapshot(&p)));
+        rest
+    }
+
+    //the isolated margin is lost whole, the exit leg pays no taker fee. Funding went into that margin
+    //(the liquidation price counts it) and is lost with it, none is received
+    fn liquidate(&mut self, pos: SimPosition, price: f64, time: u64, trades: &mut Vec<TradeInfo>) -> LiquidationFillInfo{
+        let fee = pos.size * pos.entry * self.sim.fee;
+
+        let trade = TradeInfo{
+            open: pos.entry,
+            close: price,
+            pnl: -pos.margin - fee,
+            fee,
+            funding: 0.0,
+            is_long: pos.is_long,
+            duration: Some((time - pos.open_time) / 1000),
+            oid: (pos.oid, pos.oid + 1),
+            regime: self.signal_engine.get_regime(),
+        };
+        self.margin += trade.pnl;
+        self.signal_engine.on_trade_closed(&trade);
+        self.signal_engine.update_exec_params(ExecParam::Margin(self.margin));
+        self.signal_engine.update_position(None);
+        trades.push(trade);
+
+        LiquidationFillInfo{
+            price,
+            sz: pos.size,
+            oid: pos.oid + 1,
+            is_long: pos.is_long,
+        }
+    }
+
+    fn upnl(pos: &SimPosition, price: f64) -> f64{
+        if pos.is_long {pos.size * (price - pos.entry)} else {pos.size * (pos.entry - price)}
+    }
+
+    fn snapshot(pos: &SimPosition) -> PositionSnapshot{
+        PositionSnapshot{
+            is_long: pos.is_long,
+            size: pos.size,
+            entry: pos.entry,
+            upnl: 0.0,
+            open_time: pos.open_time,
+        }
+    }
+
+}
@@ -0,0 +1,217 @@
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+
+use crate::MarginAllocation;
+use crate::trade_setup::TradeInfo;
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Resampling{
+    //same trades in a random order
+    Shuffle,
+    //trades drawn with replacement
+    Bootstrap,
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct MonteCarloConfig{
+    pub runs: usize,
+    pub method: Resampling,
+    pub seed: u64,
+    //fraction of the starting margin that counts as ruined once lost
+    pub ruin: f64,
+}
+
+impl Default for MonteCarloConfig{
+    fn default() -> Self{
+        Self{
+            runs: 1000,
+            method: Resampling::Shuffle,
+            seed: 0,
+            ruin: 0.5,
+        }
+    }
+}
+
+
+//What the trades are replayed with, leverage relative to the one they were taken at
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Sizing{
+    pub margin: f64,
+    pub lev_factor: f64,
+}
+
+impl Sizing{
+    pub fn new(alloc: MarginAllocation, total_margin: f64, lev: u32, traded_lev: u32) -> Self{
+        let margin = match alloc{
+            MarginAllocation::Alloc(f) => f * total_margin,
+            MarginAllocation::Amount(a) => a,
+        };
+        Self{
+            margin,
+            lev_factor: lev as f64 / traded_lev.max(1) as f64,
+        }
+    }
+}
+
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Distribution{
+    pub mean: f64,
+    pub p5: f64,
+    pub p25: f64,
+    pub p50: f64,
+    pub p75: f64,
+    pub p95: f64,
+    pub worst: f64,
+}
+
+impl Distribution{
+    //`worst` is the max when higher is worse (drawdowns), the min otherwise
+    fn new(mut values: Vec<f64>, higher_is_worse: bool) -> Self{
+        if values.is_empty(){
+            return Self::default();
+        }
+        values.sort_by(|a, b| a.total_cmp(b));
+        let at = |q: f64| values[((values.len() - 1) as f64 * q).round() as usize];
+        Self{
+            mean: values.iter().sum::<f64>() / values.len() as f64,
+            p5: at(0.05),
+            p25: at(0.25),
+            p50: at(0.5),
+            p75: at(0.75),
+            p95: at(0.95),
+            worst: if higher_is_worse {values[values.len() - 1]} else {values[0]},
+        }
+    }
+}
+
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MonteCarloReport{
+    pub runs: usize,
+    pub trades: usize,
+    pub sizing: Sizing,
+    //fraction of peak equity
+    pub max_drawdown: Distribution,
+    //trades from the deepest trough back to the previous peak, runs that never recover are left out
+    pub recovery_trades: Distribution,
+    //time in trades over the same span, idle time between trades isn't in TradeInfo so this is a lower bound
+    pub recovery_secs: Distribution,
+    pub unrecovered: f64,
+    pub final_margin: Distribution,
+    pub risk_of_ruin: f64,
+}
+
+
+//returns on the margin each trade was taken with, margin compounds from `start_margin`
+fn trade_returns(trades: &[TradeInfo], start_margin: f64) -> Vec<(f64, u64)>{
+    let mut margin = start_margin;
+    trades.iter().map(|t| {
+        let r = if margin > 0.0 {t.pnl / margin} else {0.0};
+        margin += t.pnl;
+        (r, t.duration.unwrap_or(0))
+    }).collect()
+}
+
+
+struct Path{
+    max_drawdown: f64,
+    recovery: Option<(usize, u64)>,
+    final_margin: f64,
+    ruined: bool,
+}
+
+fn walk(returns: &[(f64, u64)], sizing: Sizing, ruin: f64) -> Path{
+    let mut margin = sizing.margin;
+    let mut peak = margin;
+    let mut peak_at = 0;
+    let mut max_drawdown = 0.0_f64;
+    let mut trough_from: Option<usize> = None;
+    let mut recovery = None;
+    let mut ruined = false;
+
+    for (i, (r, _)) in returns.iter().enumerate(){
+        margin = (margin * (1.0 + r * sizing.lev_factor)).max(0.0);
+        if margin <= sizing.margin * (1.0 - ruin){
+            ruined = true;
+        }
+        if margin >= peak{
+            if let Some(from) = trough_from.take(){
+                let secs = returns[from..=i].iter().map(|r| r.1).sum();
+                recovery = Some((i + 1 - from, secs));
+            }
+            peak = margin;
+            peak_at = i + 1;
+        }else if peak > 0.0{
+            let dd = (peak - margin) / peak;
+            if dd > max_drawdown{
+                max_drawdown = dd;
+                //a deeper drawdown resets the recovery being measured
+                trough_from = Some(peak_at);
+                recovery = None;
+            }
+        }
+    }
+    Path{ max_drawdown, recovery, final_margin: margin, ruined }
+}
+
+
+pub fn monte_carlo(trades: &[TradeInfo], start_margin: f64, sizing: Sizing, cfg: MonteCarloConfig) -> MonteCarloReport{
+    let returns = trade_returns(trades, start_margin);
+    let mut rng = StdRng::seed_from_u64(cfg.seed);
+
+    let mut drawdowns = Vec::with_capacity(cfg.runs);
+    let mut recoveries = Vec::new();
+    let mut recovery_secs = Vec::new();
+    let mut finals = Vec::with_capacity(cfg.runs);
+    let mut ruined = 0;
+    let mut unrecovered = 0;
+
+    let mut sample = returns.clone();
+    for _ in 0..cfg.runs{
+        match cfg.method{
+            Resampling::Shuffle => sample.shuffle(&mut rng),
+            Resampling::Bootstrap => {
+                for slot in sample.iter_mut(){
+                    *slot = returns[rng.gen_range(0..returns.len())];
+                }
+            },
+        }
+        let path = walk(&sample, sizing, cfg.ruin);
+        drawdowns.push(path.max_drawdown);
+        finals.push(path.final_margin);
+        match path.recovery{
+            Some((n, secs)) => {
+                recoveries.push(n as f64);
+                recovery_secs.push(secs as f64);
+            },
+            None if path.max_drawdown > 0.0 => unrecovered += 1,
+            None => {},
+        }
+        if path.ruined{
+            ruined += 1;
+        }
+    }
+
+    let runs = cfg.runs.max(1) as f64;
+    MonteCarloReport{
+        runs: cfg.runs,
+        trades: trades.len(),
+        sizing,
+        max_drawdown: Distribution::new(drawdowns, true),
+        recovery_trades: Distribution::new(recoveries, true),
+        recovery_secs: Distribution::new(recovery_secs, true),
+        unrecovered: unrecovered as f64 / runs,
+        final_margin: Distribution::new(finals, false),
+        risk_of_ruin: ruined as f64 / runs,
+    }
+}
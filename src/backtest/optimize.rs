@@ -0,0 +1,307 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use log::{info, warn};
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use serde::{Deserialize, Serialize};
+
+use crate::{AddMarketInfo, FundingSchedule, IndexId, IndicatorKind, MarginAllocation, MarginTable};
+use crate::strategy::{Risk, Style, Stance, Strategy};
+use crate::trade_setup::TradeParams;
+
+use super::{BackTester, BacktestResult, CandleSeries, Metrics, Objective, SimParams};
+
+
+//Values to try for one indicator's main period, empty keeps the configured one
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IndicatorRange{
+    pub id: IndexId,
+    #[serde(default)]
+    pub periods: Vec<u32>,
+}
+
+
+//Every empty dimension keeps the value of the base TradeParams
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ParamSpace{
+    pub risk: Vec<Risk>,
+    pub style: Vec<Style>,
+    pub stance: Vec<Stance>,
+    pub lev: Vec<u32>,
+    pub trade_time: Vec<u64>,
+    pub indicators: Vec<IndicatorRange>,
+}
+
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Candidate{
+    pub params: TradeParams,
+    pub config: Vec<IndexId>,
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Search{
+    Grid,
+    Random{samples: usize, seed: u64},
+}
+
+
+//main period of the kind, kinds without one are returned unchanged
+fn with_period(kind: IndicatorKind, p: u32) -> IndicatorKind{
+    use IndicatorKind::*;
+    match kind{
+        Rsi(_) => Rsi(p),
+        SmaOnRsi{smoothing_length, ..} => SmaOnRsi{periods: p, smoothing_length},
+        StochRsi{k_smoothing, d_smoothing, ..} => StochRsi{periods: p, k_smoothing, d_smoothing},
+        Adx{di_length, ..} => Adx{periods: p, di_length},
+        Atr(_) => Atr(p),
+        Ema(_) => Ema(p),
+        EmaCross{long, ..} => EmaCross{short: p, long},
+        Sma(_) => Sma(p),
+        Macd{slow, signal, ..} => Macd{fast: p, slow, signal},
+        Bollinger{std_dev, ..} => Bollinger{periods: p, std_dev},
+        Keltner{atr_periods, multiplier, ..} => Keltner{periods: p, atr_periods, multiplier},
+        Donchian(_) => Donchian(p),
+        VolumeSma(_) => VolumeSma(p),
+        VolumeEma(_) => VolumeEma(p),
+        Supertrend{multiplier, ..} => Supertrend{atr_periods: p, multiplier},
+        Ichimoku{kijun, senkou, ..} => Ichimoku{tenkan: p, kijun, senkou},
+        other => other,
+    }
+}
+
+fn or_base<T: Copy>(values: &[T], base: T) -> Vec<T>{
+    if values.is_empty() {vec![base]} else {values.to_vec()}
+}
+
+
+//one choice per dimension, in the order of ParamSpace::dimensions
+fn build(base: &TradeParams, space: &ParamSpace, choice: &[usize], dims: &Dimensions) -> Candidate{
+    let mut params = base.clone();
+    let (risk, style, stance) = (dims.risk[choice[0]], dims.style[choice[1]], dims.stance[choice[2]]);
+    match &mut params.strategy{
+        Strategy::Custom(strat) => {
+            strat.risk = risk;
+            strat.style = style;
+            strat.stance = stance;
+        },
+        Strategy::Ensemble(strat) => {
+            strat.risk = risk;
+            strat.style = style;
+            strat.stance = stance;
+        },
+    }
+    params.lev = dims.lev[choice[3]];
+    params.trade_time = dims.trade_time[choice[4]];
+
+    let config = space.indicators.iter().enumerate().map(|(i, range)| {
+        let (kind, tf) = range.id;
+        match range.periods.get(choice[5 + i]){
+            Some(&p) => (with_period(kind, p), tf),
+            None => (kind, tf),
+        }
+    }).collect();
+
+    Candidate{ params, config }
+}
+
+struct Dimensions{
+    risk: Vec<Risk>,
+    style: Vec<Style>,
+    stance: Vec<Stance>,
+    lev: Vec<u32>,
+    trade_time: Vec<u64>,
+    sizes: Vec<usize>,
+}
+
+impl ParamSpace{
+
+    fn dimensions(&self, base: &TradeParams) -> Dimensions{
+        let (risk, style, stance) = match &base.strategy{
+            Strategy::Custom(s) => (s.risk, s.style, s.stance),
+            Strategy::Ensemble(s) => (s.risk, s.style, s.stance),
+        };
+        let dims = Dimensions{
+            risk: or_base(&self.risk, risk),
+            style: or_base(&self.style, style),
+            stance: or_base(&self.stance, stance),
+            lev: or_base(&self.lev, base.lev),
+            trade_time: or_base(&self.trade_time, base.trade_time),
+            sizes: Vec::new(),
+        };
+        let mut sizes = vec![dims.risk.len(), dims.style.len(), dims.stance.len(), dims.lev.len(), dims.trade_time.len()];
+        sizes.extend(self.indicators.iter().map(|r| r.periods.len().max(1)));
+        Dimensions{ sizes, ..dims }
+    }
+
+    pub fn size(&self, base: &TradeParams) -> usize{
+        self.dimensions(base).sizes.iter().product()
+    }
+
+    pub fn grid(&self, base: &TradeParams) -> Vec<Candidate>{
+        let dims = self.dimensions(base);
+        let total: usize = dims.sizes.iter().product();
+        let mut out = Vec::with_capacity(total);
+        let mut choice = vec![0usize; dims.sizes.len()];
+        for _ in 0..total{
+            out.push(build(base, self, &choice, &dims));
+            //odometer over the dimensions
+            for (digit, size) in choice.iter_mut().zip(&dims.sizes){
+                *digit += 1;
+                if *digit < *size{
+                    break;
+                }
+                *digit = 0;
+            }
+        }
+        out
+    }
+
+    pub fn sample(&self, base: &TradeParams, samples: usize, seed: u64) -> Vec<Candidate>{
+        let dims = self.dimensions(base);
+        let mut rng = StdRng::seed_from_u64(seed);
+        (0..samples).map(|_| {
+            let choice: Vec<usize> = dims.sizes.iter().map(|&size| rng.gen_range(0..size)).collect();
+            build(base, self, &choice, &dims)
+        }).collect()
+    }
+
+    pub fn candidates(&self, base: &TradeParams, search: Search) -> Vec<Candidate>{
+        match search{
+            Search::Grid => self.grid(base),
+            Search::Random{samples, seed} => self.sample(base, samples, seed),
+        }
+    }
+}
+
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OptResult{
+    pub candidate: Candidate,
+    pub metrics: Metrics,
+    pub score: f64,
+}
+
+
+//Runs one backtest per candidate over the same series, spread over `threads` OS threads
+#[derive(Debug, Clone)]
+pub struct Optimizer{
+    pub asset: String,
+    pub series: Arc<CandleSeries>,
+    pub warm_up: usize,
+    pub margin: f64,
+    pub sim: SimParams,
+    pub funding: FundingSchedule,
+    //the asset's max leverage, a flat table at it unless `margin_table` is set
+    pub max_lev: u32,
+    pub margin_table: Option<MarginTable>,
+    pub objective: Objective,
+    pub threads: usize,
+}
+
+impl Optimizer{
+
+    pub fn new(asset: &str, series: CandleSeries, warm_up: usize, margin: f64, max_lev: u32, objective: Objective) -> Self{
+        Self{
+            asset: asset.to_string(),
+            series: Arc::new(series),
+            warm_up,
+            margin,
+            sim: SimParams::default(),
+            funding: FundingSchedule::default(),
+            max_lev,
+            margin_table: None,
+            objective,
+            threads: std::thread::available_parallelism().map_or(1, |n| n.get()),
+        }
+    }
+
+    //each call gets its own current-thread runtime for the async warm-up
+    pub fn backtest(&self, candidate: &Candidate) -> BacktestResult{
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("failed to build backtest runtime");
+
+        let mut tester = BackTester::new(&self.asset, candidate.params.clone(), Some(candidate.config.clone()), self.margin, self.max_lev);
+        tester.sim = self.sim;
+        tester.funding = self.funding.clone();
+        if let Some(table) = &self.margin_table{
+            tester.margin_table = table.clone();
+        }
+        //run() leaves out candidates the series can't be resampled for, this one plays nothing
+        if let Err(e) = rt.block_on(tester.load_series(&self.series, self.warm_up)){
+            warn!("{}: {}", self.asset, e);
+        }
+        tester.run()
+    }
+
+    pub fn evaluate(&self, candidate: Candidate) -> OptResult{
+        let metrics = Metrics::new(&self.backtest(&candidate));
+
+        OptResult{
+            score: self.objective.score(&metrics),
+            candidate,
+            metrics,
+        }
+    }
+
+    //results sorted best first
+    pub fn run(&self, candidates: Vec<Candidate>) -> Vec<OptResult>{
+        let candidates: Vec<Candidate> = candidates.into_iter().filter(|c| {
+            let mut tfs = c.config.iter().map(|id| id.1).chain([c.params.time_frame]);
+            match tfs.try_for_each(|tf| self.series.check_resample(tf)){
+                Ok(()) => true,
+                Err(e) => {
+                    warn!("Skipping candidate: {}", e);
+                    false
+                },
+            }
+        }).collect();
+        let total = candidates.len();
+        info!("Optimizing {} over {} candidate(s) on {} thread(s)", self.asset, total, self.threads);
+
+        let next = AtomicUsize::new(0);
+        let mut results: Vec<OptResult> = std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..self.threads.max(1)).map(|_| scope.spawn(|| {
+                let mut local = Vec::new();
+                loop{
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(candidate) = candidates.get(i) else { break };
+                    local.push(self.evaluate(candidate.clone()));
+                }
+                local
+            })).collect();
+
+            handles.into_iter().flat_map(|h| h.join().unwrap_or_else(|_| {
+                warn!("Backtest thread panicked");
+                Vec::new()
+            })).collect()
+        });
+
+        results.sort_by(|a, b| b.score.total_cmp(&a.score));
+        results
+    }
+
+    pub fn search(&self, space: &ParamSpace, base: &TradeParams, search: Search) -> Vec<OptResult>{
+        self.run(space.candidates(base, search))
+    }
+}
+
+
+//best `n` configurations as markets ready to be added
+pub fn export_top(results: &[OptResult], n: usize, asset: &str, margin_alloc: MarginAllocation) -> Vec<AddMarketInfo>{
+    results.iter().take(n).map(|r| AddMarketInfo{
+        asset: asset.to_string(),
+        margin_alloc,
+        trade_params: r.candidate.params.clone(),
+        config: Some(r.candidate.config.clone()),
+    }).collect()
+}
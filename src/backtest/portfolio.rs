@@ -0,0 +1,264 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use hyperliquid_rust_sdk::Error;
+use log::{info, warn};
+use serde::Serialize;
+
+use crate::{AddMarketInfo, FundingSchedule, MarginAllocation, MarginTable, TradeInfo, MARKETS};
+use crate::margin::MarginBook;
+
+use super::{BackTester, BacktestResult, CandleSeries, Metrics, Objective, SimParams};
+
+
+//Several markets backtested on one timeline, sharing a simulated MarginBook
+#[derive(Debug, Clone)]
+pub struct Portfolio{
+    pub markets: Vec<AddMarketInfo>,
+    pub series: HashMap<String, Arc<CandleSeries>>,
+    pub funding: HashMap<String, FundingSchedule>,
+    //each asset's max leverage, a flat table at it unless `margin_tables` has its tiers
+    pub max_levs: HashMap<String, u32>,
+    pub margin_tables: HashMap<String, MarginTable>,
+    pub total_margin: f64,
+    pub warm_up: usize,
+    pub sim: SimParams,
+}
+
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MarketResult{
+    pub asset: String,
+    pub margin_alloc: MarginAllocation,
+    //margin the book allocated at the start
+    pub margin: f64,
+    pub metrics: Metrics,
+    pub result: BacktestResult,
+}
+
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PortfolioResult{
+    pub markets: Vec<MarketResult>,
+    //every market's trades in close order, on the portfolio equity curve
+    pub portfolio: BacktestResult,
+    pub metrics: Metrics,
+    //correlation of per-step equity returns, rows and columns in `markets` order
+    pub correlation: Vec<Vec<f64>>,
+    //margin updates the book refused, the market keeps compounding anyway like it does live
+    pub rejected_updates: usize,
+}
+
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SplitResult{
+    pub allocs: Vec<MarginAllocation>,
+    pub metrics: Metrics,
+    pub score: f64,
+}
+
+
+struct Sim{
+    asset: String,
+    alloc: MarginAllocation,
+    margin: f64,
+    tester: BackTester,
+}
+
+
+impl Portfolio{
+
+    pub fn new(total_margin: f64, warm_up: usize) -> Self{
+        Self{
+            markets: Vec::new(),
+            series: HashMap::new(),
+            funding: HashMap::new(),
+            max_levs: HashMap::new(),
+            margin_tables: HashMap::new(),
+            total_margin,
+            warm_up,
+            sim: SimParams::default(),
+        }
+    }
+
+    pub fn add(&mut self, info: AddMarketInfo, series: CandleSeries, max_lev: u32){
+        let asset = info.asset.trim().to_uppercase();
+        self.max_levs.insert(asset.clone(), max_lev);
+        self.series.insert(asset, Arc::new(series));
+        self.markets.push(info);
+    }
+
+    //markets are allocated in order, with the same rules as Bot::add_market
+    async fn load(&self, book: &mut MarginBook) -> Result<Vec<Sim>, Error>{
+        let mut sims: Vec<Sim> = Vec::with_capacity(self.markets.len());
+        for info in &self.markets{
+            let asset = info.asset.trim().to_uppercase();
+            if sims.iter().any(|s| s.asset == asset){
+                continue;
+            }
+            if !MARKETS.contains(&asset.as_str()){
+                return Err(Error::AssetNotFound);
+            }
+            let (Some(series), Some(&max_lev)) = (self.series.get(&asset), self.max_levs.get(&asset)) else {
+                return Err(Error::AssetNotFound);
+            };
+
+            let margin = book.allocate(asset.clone(), info.margin_alloc).await?;
+            let mut tester = BackTester::new(&asset, info.trade_params.clone(), info.config.clone(), margin, max_lev);
+            tester.sim = self.sim;
+            if let Some(funding) = self.funding.get(&asset){
+                tester.funding = funding.clone();
+            }
+            if let Some(table) = self.margin_tables.get(&asset){
+                tester.margin_table = table.clone();
+            }
+            tester.load_series(series, self.warm_up).await.map_err(|e| Error::Custom(format!("{}: {}", asset, e)))?;
+
+            sims.push(Sim{asset, alloc: info.margin_alloc, margin, tester});
+        }
+        Ok(sims)
+    }
+
+    pub async fn run(&self) -> Result<PortfolioResult, Error>{
+        let mut book = MarginBook::simulated(self.total_margin);
+        let mut sims = self.load(&mut book).await?;
+        let n = sims.len();
+
+        //bars of every market ordered by close time
+        let mut bars = Vec::new();
+        for (i, sim) in sims.iter_mut().enumerate(){
+            let tf = sim.tester.data_tf;
+            bars.extend(std::mem::take(&mut sim.tester.candle_data).into_iter().map(|c| (tf.next_open(c.open_time), i, c)));
+        }
+        bars.sort_by_key(|b| (b.0, b.1));
+
+        let mut last_equity: Vec<f64> = sims.iter().map(|s| s.margin).collect();
+        let mut columns: Vec<Vec<f64>> = vec![Vec::new(); n];
+        let mut equity: Vec<(u64, f64)> = Vec::new();
+        let mut rejected_updates = 0;
+        let mut trades: Vec<TradeInfo> = Vec::new();
+        let mut played: Vec<usize> = vec![0; n];
+
+        for (step, group) in bars.chunk_by(|a, b| a.0 == b.0).enumerate(){
+            for (_, i, candle) in group{
+                let sim = &mut sims[*i];
+                let closed = sim.tester.play(candle);
+                trades.extend_from_slice(closed);
+                played[*i] += closed.len();
+                if !closed.is_empty(){
+                    let pnl: f64 = closed.iter().map(|t| t.pnl).sum();
+                    //same path as MarketCommand::ReceiveTrade then MarketUpdate::MarginUpdate live
+                    book.apply_pnl(pnl);
+                    if book.update_asset((sim.asset.clone(), sim.tester.margin)).await.is_err(){
+                        rejected_updates += 1;
+                    }
+                }
+                last_equity[*i] = sim.tester.equity();
+            }
+
+            let upnl: f64 = sims.iter().zip(&last_equity).map(|(s, e)| e - s.tester.margin).sum();
+            equity.push((group[0].0, book.total_on_chain + upnl));
+            for (column, e) in columns.iter_mut().zip(&last_equity){
+                column.push(*e);
+            }
+            if step % 10_000 == 0{
+                info!("Portfolio backtest at {}", group[0].0);
+            }
+        }
+
+        let mut markets = Vec::with_capacity(n);
+        for (i, sim) in sims.iter_mut().enumerate(){
+            let before = sim.tester.margin;
+            let result = sim.tester.finish();
+            book.apply_pnl(result.end_margin - before);
+            trades.extend_from_slice(&result.trades[played[i]..]);
+            markets.push(MarketResult{
+                asset: sim.asset.clone(),
+                margin_alloc: sim.alloc,
+                margin: sim.margin,
+                metrics: Metrics::new(&result),
+                result,
+            });
+        }
+        //positions still open were closed at their last price
+        if let Some(last) = equity.last_mut(){
+            last.1 = book.total_on_chain;
+        }
+
+        let portfolio = BacktestResult{
+            start_margin: self.total_margin,
+            end_margin: book.total_on_chain,
+            tf: markets.iter().map(|m| m.result.tf).min_by_key(|tf| tf.to_millis()).unwrap_or(crate::BASE_TF),
+            trades,
+            equity,
+            liquidations: markets.iter().flat_map(|m| m.result.liquidations.iter().copied()).collect(),
+        };
+
+        Ok(PortfolioResult{
+            metrics: Metrics::new(&portfolio),
+            correlation: correlation(&columns),
+            markets,
+            portfolio,
+            rejected_updates,
+        })
+    }
+
+    //one run per split, each split gives an allocation for every market in order, best first
+    pub async fn compare_splits(&self, splits: Vec<Vec<MarginAllocation>>, objective: Objective) -> Vec<SplitResult>{
+        let mut results = Vec::with_capacity(splits.len());
+        for allocs in splits{
+            if allocs.len() != self.markets.len(){
+                warn!("Split {:?} skipped: {} allocation(s) for {} market(s)", allocs, allocs.len(), self.markets.len());
+                continue;
+            }
+            let portfolio = Portfolio{
+                markets: self.markets.iter().zip(&allocs).map(|(m, alloc)| AddMarketInfo{margin_alloc: *alloc, ..m.clone()}).collect(),
+                ..self.clone()
+            };
+            match portfolio.run().await{
+                Ok(result) => results.push(SplitResult{
+                    score: objective.score(&result.metrics),
+                    metrics: result.metrics,
+                    allocs,
+                }),
+                Err(e) => warn!("Split {:?} skipped: {}", allocs, e),
+            }
+        }
+        results.sort_by(|a, b| b.score.total_cmp(&a.score));
+        results
+    }
+}
+
+
+fn returns(equity: &[f64]) -> Vec<f64>{
+    equity.windows(2).map(|w| if w[0] > 0.0 {w[1] / w[0] - 1.0} else {0.0}).collect()
+}
+
+//pearson, 0 when either side never moves
+fn pearson(a: &[f64], b: &[f64]) -> f64{
+    let n = a.len().min(b.len()) as f64;
+    if n < 2.0{
+        return 0.0;
+    }
+    let (ma, mb) = (a.iter().sum::<f64>() / n, b.iter().sum::<f64>() / n);
+    let (mut cov, mut va, mut vb) = (0.0, 0.0, 0.0);
+    for (x, y) in a.iter().zip(b){
+        cov += (x - ma) * (y - mb);
+        va += (x - ma).powi(2);
+        vb += (y - mb).powi(2);
+    }
+    if va == 0.0 || vb == 0.0{
+        return 0.0;
+    }
+    cov / (va * vb).sqrt()
+}
+
+fn correlation(columns: &[Vec<f64>]) -> Vec<Vec<f64>>{
+    let returns: Vec<Vec<f64>> = columns.iter().map(|c| returns(c)).collect();
+    (0..returns.len()).map(|i| {
+        (0..returns.len()).map(|j| if i == j {1.0} else {pearson(&returns[i], &returns[j])}).collect()
+    }).collect()
+}
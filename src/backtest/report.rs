@@ -0,0 +1,262 @@
+use std::fmt::Write as _;
+use std::path::Path;
+
+use serde::Serialize;
+
+use super::BacktestResult;
+use super::metrics::{YEAR_MS, sharpe, sortino, max_drawdown, max_drawdown_duration};
+use super::monte_carlo::MonteCarloReport;
+use crate::trade_setup::TradeInfo;
+
+
+pub const REPORT_DIR: &str = "data/reports";
+
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SideStats{
+    pub trades: usize,
+    pub pnl: f64,
+    pub win_rate: f64,
+    pub avg_win: f64,
+    pub avg_loss: f64,
+    pub profit_factor: f64,
+    pub expectancy: f64,
+}
+
+impl SideStats{
+    fn new<'a, I: IntoIterator<Item = &'a TradeInfo>>(trades: I) -> Self{
+        let pnls: Vec<f64> = trades.into_iter().map(|t| t.pnl).collect();
+        if pnls.is_empty(){
+            return Self::default();
+        }
+        let n = pnls.len() as f64;
+        let wins: Vec<f64> = pnls.iter().copied().filter(|p| *p > 0.0).collect();
+        let losses: Vec<f64> = pnls.iter().copied().filter(|p| *p < 0.0).collect();
+        let gross_win: f64 = wins.iter().sum();
+        let gross_loss: f64 = -losses.iter().sum::<f64>();
+
+        let profit_factor = if gross_loss > 0.0{
+            gross_win / gross_loss
+        }else if gross_win > 0.0{
+            f64::INFINITY
+        }else{
+            0.0
+        };
+
+        Self{
+            trades: pnls.len(),
+            pnl: pnls.iter().sum(),
+            win_rate: wins.len() as f64 / n,
+            avg_win: if wins.is_empty() {0.0} else {gross_win / wins.len() as f64},
+            avg_loss: if losses.is_empty() {0.0} else {-gross_loss / losses.len() as f64},
+            profit_factor,
+            expectancy: pnls.iter().sum::<f64>() / n,
+        }
+    }
+}
+
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Report{
+    pub title: String,
+    pub start: u64,
+    pub end: u64,
+    pub start_margin: f64,
+    pub end_margin: f64,
+    pub total_return_pct: f64,
+    //None when the series spans less than a day
+    pub cagr_pct: Option<f64>,
+    pub sharpe: f64,
+    pub sortino: f64,
+    pub max_drawdown: f64,
+    pub max_drawdown_secs: u64,
+    pub fees: f64,
+    //net funding received, negative when paid
+    pub funding: f64,
+    //fraction of the period spent in a position, None if trades carry no duration
+    pub exposure: Option<f64>,
+    pub all: SideStats,
+    pub long: SideStats,
+    pub short: SideStats,
+    pub equity: Vec<(u64, f64)>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub monte_carlo: Option<MonteCarloReport>,
+}
+
+
+impl Report{
+
+    pub fn from_backtest(title: &str, result: &BacktestResult) -> Self{
+        Self::build(title, &result.trades, result.start_margin, &result.equity, Some(result.tf.to_millis()))
+    }
+
+    //live history, `margin` being the account margin sampled over time (at least at every close)
+    pub fn from_history(title: &str, trades: &[TradeInfo], start_margin: f64, margin: &[(u64, f64)]) -> Self{
+        Self::build(title, trades, start_margin, margin, None)
+    }
+
+    pub fn with_monte_carlo(mut self, report: MonteCarloReport) -> Self{
+        self.monte_carlo = Some(report);
+        self
+    }
+
+    fn build(title: &str, trades: &[TradeInfo], start_margin: f64, equity: &[(u64, f64)], bar_ms: Option<u64>) -> Self{
+        let start = equity.first().map_or(0, |e| e.0);
+        let end = equity.last().map_or(0, |e| e.0);
+        let span = end.saturating_sub(start);
+        let end_margin = equity.last().map_or(start_margin + trades.iter().map(|t| t.pnl).sum::<f64>(), |e| e.1);
+
+        //irregular live samples are annualized with their average spacing
+        let bar_ms = bar_ms.unwrap_or_else(|| {
+            if equity.len() > 1 {span / (equity.len() as u64 - 1)} else {0}
+        }).max(1);
+
+        let cagr_pct = if span >= 24 * 60 * 60 * 1000 && start_margin > 0.0 && end_margin > 0.0{
+            Some(100.0 * ((end_margin / start_margin).powf(YEAR_MS / span as f64) - 1.0))
+        }else{
+            None
+        };
+
+        let timed: Vec<u64> = trades.iter().filter_map(|t| t.duration).collect();
+        let exposure = if timed.is_empty() || span == 0{
+            None
+        }else{
+            Some((timed.iter().sum::<u64>() as f64 * 1000.0 / span as f64).min(1.0))
+        };
+
+        Self{
+            title: title.to_string(),
+            start,
+            end,
+            start_margin,
+            end_margin,
+            total_return_pct: if start_margin > 0.0 {100.0 * (end_margin / start_margin - 1.0)} else {0.0},
+            cagr_pct,
+            sharpe: sharpe(equity, bar_ms),
+            sortino: sortino(equity, bar_ms),
+            max_drawdown: max_drawdown(equity.iter().map(|e| e.1)),
+            max_drawdown_secs: max_drawdown_duration(equity) / 1000,
+            fees: trades.iter().map(|t| t.fee).sum(),
+            funding: trades.iter().map(|t| t.funding).sum(),
+            exposure,
+            all: SideStats::new(trades),
+            long: SideStats::new(trades.iter().filter(|t| t.is_long)),
+            short: SideStats::new(trades.iter().filter(|t| !t.is_long)),
+            equity: equity.to_vec(),
+            monte_carlo: None,
+        }
+    }
+
+    pub fn to_json(&self) -> serde_json::Result<String>{
+        serde_json::to_string_pretty(self)
+    }
+
+    //single file, no external scripts or styles
+    pub fn to_html(&self) -> String{
+        let mut html = String::with_capacity(16 * 1024);
+        let _ = write!(html,
+            "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>{title}</title><style>\
+            body{{font-family:sans-serif;background:#111;color:#ddd;margin:2em}}\
+            table{{border-collapse:collapse;margin:1em 0}}\
+            td,th{{padding:4px 12px;border-bottom:1px solid #333;text-align:right}}\
+            th:first-child,td:first-child{{text-align:left}}\
+            svg{{background:#1a1a1a}}</style></head><body><h1>{title}</h1>",
+            title = escape(&self.title),
+        );
+
+        html.push_str(&self.equity_svg(900.0, 300.0));
+
+        let fmt_opt = |v: Option<f64>, unit: &str| v.map_or("-".to_string(), |v| format!("{v:.2}{unit}"));
+        let rows = [
+            ("Start margin", format!("{:.2}", self.start_margin)),
+            ("End margin", format!("{:.2}", self.end_margin)),
+            ("Total return", format!("{:.2}%", self.total_return_pct)),
+            ("CAGR", fmt_opt(self.cagr_pct, "%")),
+            ("Sharpe", format!("{:.2}", self.sharpe)),
+            ("Sortino", format!("{:.2}", self.sortino)),
+            ("Max drawdown", format!("{:.2}%", 100.0 * self.max_drawdown)),
+            ("Max drawdown duration", fmt_duration(self.max_drawdown_secs)),
+            ("Exposure", fmt_opt(self.exposure.map(|e| 100.0 * e), "%")),
+            ("Fees paid", format!("{:.2}", self.fees)),
+            ("Funding received", format!("{:.2}", self.funding)),
+        ];
+        html.push_str("<table>");
+        for (name, value) in rows{
+            let _ = write!(html, "<tr><td>{name}</td><td>{value}</td></tr>");
+        }
+        html.push_str("</table>");
+
+        html.push_str("<table><tr><th></th><th>Trades</th><th>PnL</th><th>Win rate</th><th>Avg win</th>\
+            <th>Avg loss</th><th>Profit factor</th><th>Expectancy</th></tr>");
+        for (name, s) in [("All", &self.all), ("Long", &self.long), ("Short", &self.short)]{
+            let _ = write!(html,
+                "<tr><td>{name}</td><td>{}</td><td>{:.2}</td><td>{:.1}%</td><td>{:.2}</td><td>{:.2}</td><td>{:.2}</td><td>{:.2}</td></tr>",
+                s.trades, s.pnl, 100.0 * s.win_rate, s.avg_win, s.avg_loss, s.profit_factor, s.expectancy,
+            );
+        }
+        html.push_str("</table>");
+
+        if let Some(mc) = &self.monte_carlo{
+            let _ = write!(html, "<h2>Monte Carlo ({} runs)</h2><table>\
+                <tr><th></th><th>Mean</th><th>P5</th><th>P50</th><th>P95</th><th>Worst</th></tr>", mc.runs);
+            for (name, d, scale) in [("Max drawdown %", &mc.max_drawdown, 100.0), ("Final margin", &mc.final_margin, 1.0)]{
+                let _ = write!(html,
+                    "<tr><td>{name}</td><td>{:.2}</td><td>{:.2}</td><td>{:.2}</td><td>{:.2}</td><td>{:.2}</td></tr>",
+                    scale * d.mean, scale * d.p5, scale * d.p50, scale * d.p95, scale * d.worst,
+                );
+            }
+            let _ = write!(html, "</table><p>Risk of ruin: {:.2}%</p>", 100.0 * mc.risk_of_ruin);
+        }
+
+        html.push_str("</body></html>");
+        html
+    }
+
+    fn equity_svg(&self, width: f64, height: f64) -> String{
+        if self.equity.len() < 2{
+            return String::new();
+        }
+        let (t0, t1) = (self.start as f64, self.end.max(self.start + 1) as f64);
+        let lo = self.equity.iter().map(|e| e.1).fold(f64::MAX, f64::min);
+        let hi = self.equity.iter().map(|e| e.1).fold(f64::MIN, f64::max);
+        let range = (hi - lo).max(f64::EPSILON);
+
+        //long series are thinned so the file stays small
+        let step = (self.equity.len() / 2000).max(1);
+        let mut points = String::new();
+        for (time, value) in self.equity.iter().step_by(step).chain(self.equity.last()){
+            let x = (*time as f64 - t0) / (t1 - t0) * width;
+            let y = height - (value - lo) / range * height;
+            let _ = write!(points, "{x:.1},{y:.1} ");
+        }
+
+        format!(
+            "<svg width=\"{width}\" height=\"{height}\" viewBox=\"0 0 {width} {height}\">\
+            <polyline fill=\"none\" stroke=\"#4caf50\" stroke-width=\"1.5\" points=\"{points}\"/>\
+            <text x=\"4\" y=\"14\" fill=\"#888\" font-size=\"12\">{hi:.2}</text>\
+            <text x=\"4\" y=\"{}\" fill=\"#888\" font-size=\"12\">{lo:.2}</text></svg>",
+            height - 4.0,
+        )
+    }
+
+    //writes <dir>/<name>.json and <dir>/<name>.html
+    pub fn write(&self, dir: impl AsRef<Path>, name: &str) -> std::io::Result<()>{
+        let dir = dir.as_ref();
+        std::fs::create_dir_all(dir)?;
+        std::fs::write(dir.join(format!("{name}.json")), self.to_json()?)?;
+        std::fs::write(dir.join(format!("{name}.html")), self.to_html())?;
+        Ok(())
+    }
+}
+
+
+fn escape(s: &str) -> String{
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+fn fmt_duration(secs: u64) -> String{
+    let (d, h, m) = (secs / 86_400, secs % 86_400 / 3600, secs % 3600 / 60);
+    if d > 0 {format!("{d}d {h}h")} else {format!("{h}h {m}m")}
+}
@@ -0,0 +1,178 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use log::info;
+use serde::{Deserialize, Serialize};
+
+use crate::strategy::Strategy;
+use crate::trade_setup::TradeParams;
+
+use super::{BacktestResult, Candidate, CandleSeries, Metrics, Optimizer, ParamSpace, Search, ValidationReport};
+
+
+//Window lengths are in bars of the series, step defaults to the out-of-sample length
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WalkForwardConfig{
+    pub in_sample: usize,
+    pub out_of_sample: usize,
+    #[serde(default)]
+    pub step: Option<usize>,
+    pub search: Search,
+}
+
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WindowResult{
+    pub in_sample: (u64, u64),
+    pub out_of_sample: (u64, u64),
+    pub best: Candidate,
+    pub in_sample_metrics: Metrics,
+    pub out_of_sample_metrics: Metrics,
+    pub in_sample_score: f64,
+    pub out_of_sample_score: f64,
+}
+
+
+//How often the optimizer picked each value of a parameter across windows
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ParamStability{
+    pub param: String,
+    pub picks: BTreeMap<String, usize>,
+    //times the pick differs from the previous window
+    pub changes: usize,
+}
+
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WalkForwardReport{
+    pub windows: Vec<WindowResult>,
+    //out-of-sample windows chained, each starts with the margin the previous ended with
+    pub equity: Vec<(u64, f64)>,
+    pub metrics: Metrics,
+    //mean out-of-sample score over mean in-sample score, far below 1 hints at overfitting
+    pub efficiency: f64,
+    pub stability: Vec<ParamStability>,
+}
+
+
+fn sub_series(series: &CandleSeries, from: usize, to: usize) -> CandleSeries{
+    CandleSeries{
+        tf: series.tf,
+        candles: series.candles[from..to].to_vec(),
+        report: ValidationReport::default(),
+    }
+}
+
+fn span(series: &CandleSeries, from: usize, to: usize) -> (u64, u64){
+    (series.candles[from].open_time, series.tf.next_open(series.candles[to - 1].open_time))
+}
+
+
+impl Optimizer{
+
+    pub fn walk_forward(&self, space: &ParamSpace, base: &TradeParams, cfg: WalkForwardConfig) -> WalkForwardReport{
+        let candles = self.series.candles.len();
+        let step = cfg.step.unwrap_or(cfg.out_of_sample).max(1);
+        let candidates = space.candidates(base, cfg.search);
+
+        let mut windows = Vec::new();
+        let mut stitched = BacktestResult{
+            start_margin: self.margin,
+            end_margin: self.margin,
+            tf: self.series.tf,
+            trades: Vec::new(),
+            equity: Vec::new(),
+            liquidations: Vec::new(),
+        };
+
+        let mut start = 0;
+        while start + self.warm_up + cfg.in_sample + cfg.out_of_sample <= candles{
+            let is_end = start + self.warm_up + cfg.in_sample;
+            let oos_end = is_end + cfg.out_of_sample;
+
+            let in_sample = Optimizer{
+                series: Arc::new(sub_series(&self.series, start, is_end)),
+                ..self.clone()
+            };
+            let Some(best) = in_sample.run(candidates.clone()).into_iter().next() else { break };
+
+            //out of sample warms up on the tail of the in-sample window
+            let out_of_sample = Optimizer{
+                series: Arc::new(sub_series(&self.series, is_end - self.warm_up, oos_end)),
+                margin: stitched.end_margin,
+                ..self.clone()
+            };
+            let result = out_of_sample.backtest(&best.candidate);
+            let metrics = Metrics::new(&result);
+            info!("Walk-forward window {}: in-sample {:.3}, out-of-sample {:.3}",
+                windows.len(), best.score, self.objective.score(&metrics));
+
+            stitched.end_margin = result.end_margin;
+            stitched.trades.extend(result.trades);
+            stitched.equity.extend(result.equity);
+            stitched.liquidations.extend(result.liquidations);
+
+            windows.push(WindowResult{
+                in_sample: span(&self.series, start + self.warm_up, is_end),
+                out_of_sample: span(&self.series, is_end, oos_end),
+                best: best.candidate,
+                in_sample_metrics: best.metrics,
+                out_of_sample_score: self.objective.score(&metrics),
+                out_of_sample_metrics: metrics,
+                in_sample_score: best.score,
+            });
+            start += step;
+        }
+
+        let scored: Vec<&WindowResult> = windows.iter().filter(|w| w.in_sample_score > f64::MIN && w.out_of_sample_score > f64::MIN).collect();
+        let mean = |f: fn(&WindowResult) -> f64| scored.iter().map(|w| f(w)).sum::<f64>() / scored.len().max(1) as f64;
+        let is_mean = mean(|w| w.in_sample_score);
+        let efficiency = if is_mean != 0.0 {mean(|w| w.out_of_sample_score) / is_mean} else {0.0};
+
+        WalkForwardReport{
+            metrics: Metrics::new(&stitched),
+            equity: stitched.equity,
+            efficiency,
+            stability: stability(&windows),
+            windows,
+        }
+    }
+}
+
+
+fn stability(windows: &[WindowResult]) -> Vec<ParamStability>{
+    let mut params: Vec<(String, Vec<String>)> = Vec::new();
+    let mut push = |name: String, value: String| match params.iter_mut().find(|p| p.0 == name){
+        Some(p) => p.1.push(value),
+        None => params.push((name, vec![value])),
+    };
+
+    for w in windows{
+        let p = &w.best.params;
+        let (risk, style, stance) = match &p.strategy{
+            Strategy::Custom(s) => (s.risk, s.style, s.stance),
+            Strategy::Ensemble(s) => (s.risk, s.style, s.stance),
+        };
+        push("risk".into(), format!("{:?}", risk));
+        push("style".into(), format!("{:?}", style));
+        push("stance".into(), format!("{:?}", stance));
+        push("lev".into(), p.lev.to_string());
+        push("tradeTime".into(), p.trade_time.to_string());
+        for (i, (kind, tf)) in w.best.config.iter().enumerate(){
+            push(format!("indicator{}", i), format!("{:?} {}", kind, tf.as_str()));
+        }
+    }
+
+    params.into_iter().map(|(param, values)| {
+        let mut picks = BTreeMap::new();
+        for v in &values{
+            *picks.entry(v.clone()).or_insert(0) += 1;
+        }
+        let changes = values.windows(2).filter(|w| w[0] != w[1]).count();
+        ParamStability{ param, picks, changes }
+    }).collect()
+}
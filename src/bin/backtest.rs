@@ -0,0 +1,278 @@
+use std::{
+    env, fs,
+    error::Error,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, NaiveDate};
+use log::{info, warn};
+use serde::Deserialize;
+
+use hyperliquid_rust_sdk::InfoClient;
+use hyperliquid_rust_bot::{
+    BackTester, BaseUrl, Candle, CandleStore, FundingSchedule, IndexId, MarginTable, MarginTier,
+    TimeFrame, TradeInfo, TradeParams, MARKETS, STORE_DIR,
+};
+use hyperliquid_rust_bot::backtest::{
+    CandleSeries, ImportConfig, MonteCarloConfig, Report, SimParams, Sizing, read_candles,
+};
+use hyperliquid_rust_bot::helper::{candles_snapshot, get_asset, get_time_now};
+
+
+const OUTPUT_DIR: &str = "data/backtests";
+
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RunConfig{
+    asset: String,
+    //RFC 3339 or YYYY-MM-DD (UTC), warm-up bars are taken before `start`
+    start: String,
+    //defaults to now
+    end: Option<String>,
+    margin: f64,
+    #[serde(default = "default_warm_up")]
+    warm_up: usize,
+    //defaults to data/backtests/<config file name>
+    output: Option<PathBuf>,
+    data: DataSource,
+    params: TradeParams,
+    //the engine has no default set, at least one is required
+    indicators: Vec<IndexId>,
+    #[serde(default)]
+    cost: CostModel,
+    monte_carlo: Option<MonteCarloConfig>,
+}
+
+fn default_warm_up() -> usize{
+    500
+}
+
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "source")]
+enum DataSource{
+    //CSV or Parquet file
+    File{path: PathBuf, import: ImportConfig},
+    //candles recorded by the bot
+    Store{tf: TimeFrame, root: Option<PathBuf>},
+    //candleSnapshot, the API only serves the most recent 5000 bars of each timeframe
+    Hyperliquid{tf: TimeFrame},
+}
+
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct CostModel{
+    fee: f64,
+    slippage: f64,
+    //fetch the asset's funding history for the range
+    funding: bool,
+    //margin tiers win over max_lev, neither = the asset's max leverage from the exchange
+    max_lev: Option<u32>,
+    tiers: Option<Vec<MarginTier>>,
+}
+
+impl Default for CostModel{
+    fn default() -> Self{
+        let sim = SimParams::default();
+        Self{
+            fee: sim.fee,
+            slippage: sim.slippage,
+            funding: false,
+            max_lev: None,
+            tiers: None,
+        }
+    }
+}
+
+
+struct Summary{
+    name: String,
+    report: Report,
+    liquidations: usize,
+}
+
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>>{
+    env_logger::init();
+
+    let paths: Vec<String> = env::args().skip(1).collect();
+    if paths.is_empty(){
+        eprintln!("usage: backtest <config.toml>...");
+        std::process::exit(2);
+    }
+
+    let mut summaries = Vec::with_capacity(paths.len());
+    for path in &paths{
+        match run(Path::new(path)).await{
+            Ok(summary) => summaries.push(summary),
+            Err(e) => eprintln!("{}: {}", path, e),
+        }
+    }
+
+    print_table(&summaries);
+    if summaries.len() < paths.len(){
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+
+async fn run(path: &Path) -> Result<Summary, Box<dyn Error>>{
+    let cfg: RunConfig = toml::from_str(&fs::read_to_string(path)?)?;
+    if !MARKETS.contains(&cfg.asset.as_str()){
+        return Err(format!("{} isn't a tradable asset", cfg.asset).into());
+    }
+    if cfg.indicators.is_empty(){
+        return Err("indicators is empty, list at least one (IndicatorKind, TimeFrame) pair".into());
+    }
+    let name = path.file_stem().and_then(|s| s.to_str()).unwrap_or("backtest").to_string();
+    let start = parse_time(&cfg.start)?;
+    let end = cfg.end.as_deref().map(parse_time).transpose()?.unwrap_or_else(get_time_now);
+    if end <= start{
+        return Err(format!("end {} is before start {}", end, start).into());
+    }
+
+    let tf = match &cfg.data{
+        DataSource::File{import, ..} => import.tf,
+        DataSource::Store{tf, ..} | DataSource::Hyperliquid{tf} => *tf,
+    };
+    let from = start.saturating_sub(cfg.warm_up as u64 * tf.to_millis());
+
+    let candles: Vec<Candle> = match &cfg.data{
+        DataSource::File{path, import} => read_candles(path, import)?.candles,
+        DataSource::Store{tf, root} => {
+            let store = CandleStore::new(root.clone().unwrap_or_else(|| PathBuf::from(STORE_DIR)));
+            store.read(&cfg.asset, *tf, usize::MAX).await?
+        },
+        DataSource::Hyperliquid{tf} => fetch_candles(&cfg.asset, *tf, from, end).await?,
+    };
+    //warm-up is whatever comes before `start`, the test bars always begin there
+    let (mut before, test): (Vec<Candle>, Vec<Candle>) = candles.into_iter()
+        .filter(|c| c.open_time < end)
+        .partition(|c| c.open_time < start);
+    if test.is_empty(){
+        return Err(format!("no {} bars between start and end", tf.as_str()).into());
+    }
+    let warm_up = before.len().min(cfg.warm_up);
+    if warm_up < cfg.warm_up{
+        warn!("{}: only {} of {} warm-up bar(s) before the start", name, warm_up, cfg.warm_up);
+    }
+    let mut candles = before.split_off(before.len() - warm_up);
+    candles.extend(test);
+    let series = CandleSeries::new(tf, candles)?;
+    info!("{}: {} bar(s) of {} {}", name, series.candles.len(), cfg.asset, tf.as_str());
+
+    let config = Some(cfg.indicators.clone());
+    let margin_table = match (&cfg.cost.tiers, cfg.cost.max_lev){
+        (Some(tiers), _) => MarginTable::new(tiers.clone()),
+        (None, Some(max_lev)) => MarginTable::flat(max_lev),
+        (None, None) => {
+            let client = InfoClient::new(None, Some(BaseUrl::Mainnet)).await?;
+            MarginTable::flat(get_asset(&client, &cfg.asset).await?.max_leverage)
+        },
+    };
+    let mut tester = BackTester::new(&cfg.asset, cfg.params.clone(), config, cfg.margin, margin_table.max_lev(0.0));
+    tester.sim = SimParams{fee: cfg.cost.fee, slippage: cfg.cost.slippage};
+    tester.margin_table = margin_table;
+    if cfg.cost.funding{
+        let client = InfoClient::new(None, Some(BaseUrl::Mainnet)).await?;
+        tester.funding = FundingSchedule::fetch(&client, &cfg.asset, from, end).await?;
+    }
+
+    tester.load_series(&series, warm_up).await?;
+    let result = tester.run();
+
+    let mut report = result.report(&format!("{} {} ({})", cfg.asset, tf.as_str(), name));
+    if let Some(mc) = cfg.monte_carlo{
+        report = report.with_monte_carlo(result.monte_carlo(Sizing{margin: cfg.margin, lev_factor: 1.0}, mc));
+    }
+
+    let out = cfg.output.clone().unwrap_or_else(|| Path::new(OUTPUT_DIR).join(&name));
+    report.write(&out, "report")?;
+    write_trades(&out.join("trades.csv"), &result.trades)?;
+    fs::copy(path, out.join("config.toml"))?;
+    info!("{}: report written to {}", name, out.display());
+
+    Ok(Summary{
+        name,
+        report,
+        liquidations: result.liquidations.len(),
+    })
+}
+
+
+//pages through candleSnapshot, each call returns at most 5000 bars
+async fn fetch_candles(coin: &str, tf: TimeFrame, start: u64, end: u64) -> Result<Vec<Candle>, Box<dyn Error>>{
+    let client = InfoClient::new(None, Some(BaseUrl::Mainnet)).await?;
+    let mut candles: Vec<Candle> = Vec::new();
+    let mut from = start;
+    while from < end{
+        let page = candles_snapshot(&client, coin, tf, from, end).await?;
+        let Some(next) = page.last().map(|c| tf.next_open(c.open_time)) else { break };
+        candles.extend(page);
+        if next <= from{
+            break;
+        }
+        from = next;
+    }
+    Ok(candles)
+}
+
+
+fn parse_time(s: &str) -> Result<u64, String>{
+    if let Ok(time) = DateTime::parse_from_rfc3339(s){
+        return Ok(time.timestamp_millis() as u64);
+    }
+    NaiveDate::parse_from_str(s, "%Y-%m-%d")
+        .map(|d| d.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc().timestamp_millis() as u64)
+        .map_err(|e| format!("bad date {:?}: {}", s, e))
+}
+
+
+fn write_trades(path: &Path, trades: &[TradeInfo]) -> Result<(), Box<dyn Error>>{
+    let mut writer = csv::Writer::from_path(path)?;
+    writer.write_record(["side", "open", "close", "pnl", "fee", "funding", "durationSecs", "regime"])?;
+    for t in trades{
+        writer.write_record([
+            (if t.is_long {"long"} else {"short"}).to_string(),
+            t.open.to_string(),
+            t.close.to_string(),
+            t.pnl.to_string(),
+            t.fee.to_string(),
+            t.funding.to_string(),
+            t.duration.map_or(String::new(), |d| d.to_string()),
+            t.regime.map_or(String::new(), |r| format!("{:?}", r)),
+        ])?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+
+fn print_table(summaries: &[Summary]){
+    if summaries.is_empty(){
+        return;
+    }
+    let width = summaries.iter().map(|s| s.name.len()).max().unwrap_or(0).max(6);
+    println!("{:<width$} {:>7} {:>9} {:>8} {:>7} {:>8} {:>7} {:>6} {:>10} {:>10} {:>5}",
+        "config", "trades", "return%", "cagr%", "sharpe", "sortino", "maxdd%", "win%", "fees", "funding", "liqs");
+    for s in summaries{
+        let r = &s.report;
+        println!("{:<width$} {:>7} {:>9.2} {:>8} {:>7.2} {:>8.2} {:>7.2} {:>6.1} {:>10.2} {:>10.2} {:>5}",
+            s.name,
+            r.all.trades,
+            r.total_return_pct,
+            r.cagr_pct.map_or("-".to_string(), |c| format!("{:.2}", c)),
+            r.sharpe,
+            r.sortino,
+            100.0 * r.max_drawdown,
+            100.0 * r.all.win_rate,
+            r.fees,
+            r.funding,
+            s.liquidations,
+        );
+    }
+}
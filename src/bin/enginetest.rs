@@ -20,7 +20,7 @@ use hyperliquid_rust_bot::{
     AssetMargin,
     BotToMarket,
     MarketCommand,
-    IndexId, Entry, EditType, IndicatorKind,
+    IndexId, Entry, EditType, IndicatorKind, SignalFilter, WatchdogConfig,
     MARKETS,
     TradeParams,TimeFrame, AddMarketInfo, UpdateFrontend,
 
@@ -54,6 +54,8 @@ async fn main() -> Result<(), Error>{
         lev: 20,
         trade_time: 300,
         time_frame: TimeFrame::from_str("5m").unwrap_or(TimeFrame::Min1),
+        signal_filter: SignalFilter::default(),
+        watchdog: WatchdogConfig::default(),
     
     };
 
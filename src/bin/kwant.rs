@@ -26,6 +26,9 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let wallet = Wallet::new(url, pubkey, wallet).await?;
 
     let (mut bot, cmd_sender) = Bot::new(wallet).await?;
+    if let Ok(path) = env::var("RECORD_FILE"){
+        bot.enable_recording(path, env::var("RECORD_BOOK").is_ok());
+    }
     let (update_tx, mut update_rx) = unbounded_channel::<UpdateFrontend>();
     tokio::spawn(async move { bot.start(update_tx).await });
 
@@ -9,9 +9,11 @@ use crate::{Market,
     TradeParams,TradeInfo,
     Wallet, IndexId, LiquidationFillInfo,
     UpdateFrontend, AddMarketInfo, MarketInfo,
+    BASE_TF,
 };
 
 use crate::helper::{get_asset, subscribe_candles};
+use crate::recorder::Recorder;
 use tokio::{
     sync::mpsc::{Sender, UnboundedSender, UnboundedReceiver, unbounded_channel},
 };
@@ -38,6 +40,7 @@ pub struct Bot{
     update_rv: Option<UnboundedReceiver<MarketUpdate>>,
     update_tx: UnboundedSender<MarketUpdate>,
     app_tx: Option<UnboundedSender<UpdateFrontend>>,
+    recorder: Option<Recorder>,
 }
 
 
@@ -63,10 +66,20 @@ impl Bot{
             update_rv: Some(update_rv),
             update_tx,
             app_tx: None,
+            recorder: None,
         }, bot_tx))
     }
 
 
+    //writes the raw candle and user fill frames of every market to `path`, with `book` also their
+    //l2 book and trades, see Replayer
+    pub fn enable_recording<P: Into<std::path::PathBuf>>(&mut self, path: P, book: bool){
+        let recorder = Recorder::spawn(path, self.wallet.url, book);
+        recorder.watch_user(address(&self.wallet.pubkey));
+        self.recorder = Some(recorder);
+    }
+
+
     pub async fn add_market(&mut self, info: AddMarketInfo, margin_book: &Arc<Mutex<MarginBook>>) -> Result<(), Error>{
        
         let AddMarketInfo {
@@ -91,14 +104,18 @@ impl Bot{
         let margin = book.allocate(asset.clone(), margin_alloc).await?;
         
         let meta = get_asset(&self.info_client, asset_str).await?;
-        let (sub_id, mut receiver) = subscribe_candles(&mut self.info_client,
+        let (sub_id, receiver) = subscribe_candles(&mut self.info_client,
                                                         asset_str,
-                                                        trade_params.time_frame.as_str())
+                                                        BASE_TF.as_str())
                                                         .await?;
+        if let Some(recorder) = &self.recorder{
+            recorder.watch_market(asset_str);
+        }
 
         
-        let (market, market_tx) = Market::new(
+        let (mut market, market_tx) = Market::new(
             self.wallet.wallet.clone(),
+            address(&self.wallet.pubkey),
             self.wallet.url,
             self.update_tx.clone(),
             receiver,
@@ -108,6 +125,9 @@ impl Bot{
             trade_params,
             config,
         ).await?;
+        if let Some(recorder) = &self.recorder{
+            market.set_recorder(recorder.clone());
+        }
 
 
         self.markets.insert(asset.clone(), market_tx);
@@ -137,6 +157,9 @@ impl Bot{
       
         if let Some(sub_id) = self.candle_subs.remove(&asset){
             let _ = self.info_client.unsubscribe(sub_id).await?;
+            if let Some(recorder) = &self.recorder{
+                recorder.unwatch_market(&asset);
+            }
             info!("Removed {} market successfully", asset);
         }else{
             info!("Couldn't remove {} market, it doesn't exist", asset);
@@ -212,8 +235,11 @@ impl Bot{
     }
     pub async fn close_all(&mut self){
         info!("CLOSING ALL MARKETS");
-        for (_asset, id) in self.candle_subs.drain(){
+        for (asset, id) in self.candle_subs.drain(){
                 self.info_client.unsubscribe(id).await;
+                if let Some(recorder) = &self.recorder{
+                    recorder.unwatch_market(&asset);
+                }
             } 
         self.candle_subs.clear();
         for (_asset, tx) in self.markets.drain(){
@@ -0,0 +1,165 @@
+use kwant::indicators::Price;
+use serde::{Deserialize, Serialize};
+
+use crate::{TimeFrame, BASE_TF};
+
+
+//OHLCV bar as delivered by Hyperliquid, open_time is the bar's open timestamp in ms
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Candle{
+    pub open_time: u64,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    pub volume: f64,
+    pub trades: u64,
+}
+
+
+impl Candle{
+
+    pub fn price(&self) -> Price{
+        Price{
+            open: self.open,
+            high: self.high,
+            low: self.low,
+            close: self.close,
+        }
+    }
+
+    pub fn typical_price(&self) -> f64{
+        (self.high + self.low + self.close) / 3.0
+    }
+}
+
+
+impl From<Candle> for Price{
+    fn from(candle: Candle) -> Self{
+        candle.price()
+    }
+}
+
+
+//Builds bars of `tf` out of a finer base stream (1m), every update returns the bar so far
+#[derive(Debug, Clone)]
+pub struct CandleAggregator{
+    tf: TimeFrame,
+    //base candles already finished inside the current bar
+    closed: Option<Candle>,
+    //latest update of the base candle still open
+    base: Option<Candle>,
+    //open time of the last base candle already inside a seed, its volume and trades aren't added again
+    seeded: Option<u64>,
+}
+
+
+impl CandleAggregator{
+
+    pub fn new(tf: TimeFrame) -> Self{
+        Self{
+            tf,
+            closed: None,
+            base: None,
+            seeded: None,
+        }
+    }
+
+    pub fn get_tf(&self) -> TimeFrame{
+        self.tf
+    }
+
+    fn bar_open(&self, time: u64) -> u64{
+        self.tf.bar_open(time)
+    }
+
+    //starts from a partial bar of `tf`, e.g. the last candle of a snapshot taken at `taken_at`:
+    //base candles up to the one open at `taken_at` only move high, low and close
+    pub fn seed(&mut self, bar: Candle, taken_at: u64){
+        let base_ms = BASE_TF.to_millis();
+        self.closed = Some(bar);
+        self.base = None;
+        self.seeded = Some(taken_at - taken_at % base_ms);
+    }
+
+    fn fold(&self, bar: Option<Candle>, next: Candle) -> Candle{
+        if self.seeded.is_some_and(|t| next.open_time <= t){
+            merge_price(bar, next)
+        }else{
+            merge(bar, next)
+        }
+    }
+
+    pub fn update(&mut self, base: Candle) -> Candle{
+        let bar = self.bar_open(base.open_time);
+
+        if let Some(prev) = self.base{
+            if base.open_time < prev.open_time{
+                //late update of a base candle already folded in
+                return self.current().unwrap_or(base);
+            }
+            if base.open_time > prev.open_time{
+                self.closed = if self.bar_open(prev.open_time) == bar{
+                    Some(self.fold(self.closed, prev))
+                }else{
+                    None
+                };
+            }
+        }
+        if self.closed.is_some_and(|c| self.bar_open(c.open_time) != bar){
+            self.closed = None;
+            self.seeded = None;
+        }
+
+        self.base = Some(base);
+        let mut candle = self.fold(self.closed, base);
+        candle.open_time = bar;
+        candle
+    }
+
+    pub fn current(&self) -> Option<Candle>{
+        match (self.closed, self.base){
+            (closed, Some(base)) => {
+                let mut candle = self.fold(closed, base);
+                candle.open_time = self.bar_open(base.open_time);
+                Some(candle)
+            },
+            (closed, None) => closed,
+        }
+    }
+
+    pub fn reset(&mut self){
+        self.closed = None;
+        self.base = None;
+        self.seeded = None;
+    }
+}
+
+
+fn merge(bar: Option<Candle>, next: Candle) -> Candle{
+    match bar{
+        Some(bar) => Candle{
+            open_time: bar.open_time,
+            open: bar.open,
+            high: bar.high.max(next.high),
+            low: bar.low.min(next.low),
+            close: next.close,
+            volume: bar.volume + next.volume,
+            trades: bar.trades + next.trades,
+        },
+        None => next,
+    }
+}
+
+//`next` is already counted in `bar`'s volume and trades
+fn merge_price(bar: Option<Candle>, next: Candle) -> Candle{
+    match bar{
+        Some(bar) => Candle{
+            volume: bar.volume,
+            trades: bar.trades,
+            ..merge(Some(bar), next)
+        },
+        None => next,
+    }
+}
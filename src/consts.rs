@@ -1 +1,6 @@
+use crate::TimeFrame;
+
 pub const MAX_HISTORY: usize = 10000;
+
+//every market streams this timeframe, trackers aggregate their own bars from it
+pub const BASE_TF: TimeFrame = TimeFrame::Min1;
@@ -2,19 +2,26 @@ use std::sync::Arc;
 
 
 use ethers::signers::LocalWallet;
+use ethers::types::H160;
 use flume::Receiver;
-use log::info;
+use log::{info, warn};
 use tokio::{
     sync::{mpsc::Sender, Mutex},
     time::{sleep, Duration},
 };
 
 use hyperliquid_rust_sdk::{
-    Error,BaseUrl, ExchangeClient, ExchangeDataStatus, ExchangeResponseStatus, MarketOrderParams,
+    Error,BaseUrl, ExchangeClient, ExchangeDataStatus, ExchangeResponseStatus, MarketOrderParams, InfoClient,
 };
 
-use crate::trade_setup::{TradeCommand, TradeFillInfo, TradeInfo, LiquidationFillInfo};
+use crate::trade_setup::{TradeCommand, TradeFillInfo, TradeInfo, LiquidationFillInfo, PositionSnapshot};
 use crate::market::MarketCommand;
+use crate::helper::get_time_now;
+use crate::FUNDING_INTERVAL_MS;
+
+
+//settlements take a moment to show up in the user state
+const FUNDING_REFRESH_DELAY_MS: u64 = 15_000;
 
 
 
@@ -24,9 +31,15 @@ pub struct Executor {
     market_tx: Sender<MarketCommand>,
     asset: String,
     exchange_client: Arc<ExchangeClient>,
+    info_client: Arc<InfoClient>,
+    user: H160,
     is_paused: bool,
     fees: (f64, f64),
     open_position: Arc<Mutex<Option<TradeFillInfo>>>,
+    //kept across partial closes, the remainder is still the same position
+    open_time: u64,
+    //funding received by the open position, refreshed after every settlement so closes don't wait on it
+    funding: Arc<Mutex<f64>>,
 }
 
 
@@ -35,6 +48,7 @@ impl Executor {
 
     pub async fn new(
         wallet: LocalWallet,
+        user: H160,
         asset: String,
         fees: (f64, f64),
         trade_rv: Receiver<TradeCommand>, 
@@ -42,14 +56,19 @@ impl Executor {
     ) -> Result<Executor, Error>{
         
         let exchange_client = Arc::new(ExchangeClient::new(None, wallet, Some(BaseUrl::Mainnet), None, None).await?);
+        let info_client = Arc::new(InfoClient::new(None, Some(BaseUrl::Mainnet)).await?);
         Ok(Executor{
             trade_rv,
             market_tx,
             asset,
             exchange_client,
+            info_client,
+            user,
             is_paused: false,
             fees,
             open_position: Arc::new(Mutex::new(None)),
+            open_time: 0,
+            funding: Arc::new(Mutex::new(0.0)),
         })
     }
 
@@ -78,6 +97,40 @@ impl Executor {
         Ok(status)
 
     }
+    //funding the open position received since it was opened, None keeps the cached value
+    async fn position_funding(client: &InfoClient, user: H160, asset: &str) -> Option<f64>{
+        match client.user_state(user).await{
+            Ok(state) => Some(state.asset_positions.iter()
+                .find(|p| p.position.coin == asset)
+                .and_then(|p| p.position.cum_funding.since_open.parse::<f64>().ok())
+                //reported as paid, flip it to received
+                .map_or(0.0, |paid| -paid)),
+            Err(e) => {
+                warn!("Failed to fetch funding for {}: {}", asset, e);
+                None
+            },
+        }
+    }
+
+    //funding only moves at the hourly settlement, reading it once after each one keeps the cache exact
+    async fn track_funding(client: Arc<InfoClient>, user: H160, asset: String, position: Arc<Mutex<Option<TradeFillInfo>>>, funding: Arc<Mutex<f64>>){
+        loop{
+            let now = get_time_now();
+            let next = (now.saturating_sub(FUNDING_REFRESH_DELAY_MS) / FUNDING_INTERVAL_MS + 1) * FUNDING_INTERVAL_MS + FUNDING_REFRESH_DELAY_MS;
+            sleep(Duration::from_millis(next - now)).await;
+            if position.lock().await.is_none(){
+                continue;
+            }
+            if let Some(received) = Self::position_funding(&client, user, &asset).await{
+                *funding.lock().await = received;
+            }
+        }
+    }
+
+    async fn reset_funding(&self){
+        *self.funding.lock().await = 0.0;
+    }
+
     pub async fn open_order(&self,size: f64, is_long: bool) -> Result<TradeFillInfo, String>{
         
         let market_open_params = MarketOrderParams {
@@ -173,23 +226,41 @@ impl Executor {
 }
 
 
-        fn get_trade_info(open: TradeFillInfo, close: TradeFillInfo, fees: &(f64, f64)) -> TradeInfo{
+        fn get_trade_info(open: TradeFillInfo, close: TradeFillInfo, fees: &(f64, f64), funding: f64) -> TradeInfo{
             let is_long = open.is_long;
             let (fee, pnl) = Self::calculate_pnl(fees,is_long, &open, &close);
 
             TradeInfo{
                 open: open.price,
                 close: close.price,
-                pnl,
+                pnl: pnl + funding,
                 fee,
+                funding,
                 is_long, 
                 duration: None,
                 oid: (open.oid, close.oid),
+                regime: None,
             }
         }
 
 
-     
+        //splits a close fill off the open position, funding is shared pro rata with what stays open
+        fn split_close(open: TradeFillInfo, close: TradeFillInfo, fees: &(f64, f64), funding: f64) -> (TradeInfo, Option<TradeFillInfo>, f64){
+            let sz = close.sz.min(open.sz);
+            let closed_funding = funding * sz / open.sz;
+            let rest = open.sz - sz;
+            let remainder = (rest > 0.0).then(|| TradeFillInfo{sz: rest, ..open.clone()});
+            let trade_info = Self::get_trade_info(TradeFillInfo{sz, ..open}, close, fees, closed_funding);
+            (trade_info, remainder, funding - closed_funding)
+        }
+
+        fn snapshot(position: &Option<TradeFillInfo>, open_time: u64) -> Option<PositionSnapshot>{
+            position.as_ref().map(|p| PositionSnapshot{open_time, ..PositionSnapshot::new(p)})
+        }
+
+        async fn position_snapshot(&self) -> Option<PositionSnapshot>{
+            Self::snapshot(&self.open_position.lock().await, self.open_time)
+        }
 
 
     fn calculate_pnl(fees: &(f64, f64) ,is_long: bool, trade_fill_open: &TradeFillInfo, trade_fill_close: &TradeFillInfo) -> (f64, f64){
@@ -206,13 +277,23 @@ impl Executor {
     }
 
 
+    //whatever the close doesn't fill stays open, callers report the position with position_snapshot
     pub async fn cancel_trade(&mut self) -> Option<TradeInfo>{
 
-            if let Some(pos) = self.open_position.lock().await.take(){
-                let trade_fill = self.close_order(pos.sz, pos.is_long).await;
-                if let Ok(close) = trade_fill{
-                    let trade_info = Self::get_trade_info(pos, close, &self.fees);
-                    return Some(trade_info);
+            let maybe_open = self.open_position.lock().await.take();
+            if let Some(pos) = maybe_open{
+                match self.close_order(pos.sz, pos.is_long).await{
+                    Ok(close) => {
+                        let funding = *self.funding.lock().await;
+                        let (trade_info, rest, rest_funding) = Self::split_close(pos, close, &self.fees, funding);
+                        *self.funding.lock().await = rest_funding;
+                        *self.open_position.lock().await = rest;
+                        return Some(trade_info);
+                    },
+                    Err(e) => {
+                        warn!("Failed to close {} position, keeping it: {}", self.asset, e);
+                        *self.open_position.lock().await = Some(pos);
+                    },
                 }
         }
         
@@ -233,6 +314,13 @@ impl Executor {
         println!("EXECUTOR STARTED");
              
             let info_sender = self.market_tx.clone();
+            let funding_task = tokio::spawn(Self::track_funding(
+                self.info_client.clone(),
+                self.user,
+                self.asset.clone(),
+                self.open_position.clone(),
+                self.funding.clone(),
+            ));
             while let Ok(cmd) = self.trade_rv.recv_async().await{
 
                 match cmd{
@@ -241,16 +329,21 @@ impl Executor {
                                 if self.is_active().await || self.is_paused{continue};
                                 let trade_info = self.open_order(size, is_long).await;
                                 if let Ok(trade_fill) = trade_info{ 
+                                    self.reset_funding().await;
+                                    self.open_time = get_time_now();
                                     { 
                                         let mut pos = self.open_position.lock().await; 
                                         *pos = Some(trade_fill.clone()); 
                                     }         
+                                    let _ = info_sender.send(MarketCommand::UpdatePosition(Some(PositionSnapshot::new(&trade_fill)))).await;
 
                                     let client = self.exchange_client.clone();
+                                    let funding_handle = self.funding.clone();
                                     let asset = self.asset.clone();
                                     let fees = self.fees;
                                     let sender = info_sender.clone();
                                     let pos_handle = self.open_position.clone();
+                                    let open_time = self.open_time;
                                     tokio::spawn(async move{ 
                                         let _ = sleep(Duration::from_secs(duration)).await;
                                         let maybe_open = {
@@ -259,17 +352,23 @@ impl Executor {
                                         }; 
 
                                         if let Some(open) = maybe_open{
-                                          
-                                            let close_fill = Self::close_order_static(client, asset, open.sz, is_long).await;
-                                            if let Ok(fill) = close_fill{
-                                                let trade_info = Self::get_trade_info(
-                                                                                open,
-                                                                                fill,
-                                                                                &fees);
-                                                  
-                                                
-                                                let _ = sender.send(MarketCommand::ReceiveTrade(trade_info)).await;
-                                                info!("Trade Closed: {:?}", trade_info);
+                                            let close_fill = Self::close_order_static(client, asset.clone(), open.sz, is_long).await;
+                                            match close_fill{
+                                                Ok(fill) => {
+                                                    let funding = *funding_handle.lock().await;
+                                                    let (trade_info, rest, rest_funding) = Self::split_close(open, fill, &fees, funding);
+                                                    *funding_handle.lock().await = rest_funding;
+                                                    let snapshot = Self::snapshot(&rest, open_time);
+                                                    *pos_handle.lock().await = rest;
+
+                                                    let _ = sender.send(MarketCommand::ReceiveTrade(trade_info)).await;
+                                                    let _ = sender.send(MarketCommand::UpdatePosition(snapshot)).await;
+                                                    info!("Trade Closed: {:?}", trade_info);
+                                                },
+                                                Err(e) => {
+                                                    warn!("Failed to close {} position, keeping it: {}", asset, e);
+                                                    *pos_handle.lock().await = Some(open);
+                                                },
                                             }
                                     
                                     }
@@ -286,6 +385,9 @@ impl Executor {
 
                                  if let Ok(trade) = trade_fill{
                                      info!("Trade Opened: {:?}", trade.clone());
+                                     self.reset_funding().await;
+                                     self.open_time = get_time_now();
+                                     let _ = info_sender.send(MarketCommand::UpdatePosition(Some(PositionSnapshot::new(&trade)))).await;
                                      *self.open_position.lock().await = Some(trade);
                                     };
                     }else if self.is_active().await{
@@ -306,13 +408,20 @@ impl Executor {
                                 let size = size.min(open_pos.sz);
                                 let trade_fill = self.close_order(size,open_pos.is_long).await;
 
-                                if let Ok(fill) = trade_fill{
-                                    let trade_info = Self::get_trade_info(
-                                                        open_pos,
-                                                        fill,
-                                                        &self.fees);
-                                    let _ = info_sender.send(MarketCommand::ReceiveTrade(trade_info)).await;
-                                    info!("Trade Closed: {:?}", trade_info);
+                                match trade_fill{
+                                    Ok(fill) => {
+                                        let funding = *self.funding.lock().await;
+                                        let (trade_info, rest, rest_funding) = Self::split_close(open_pos, fill, &self.fees, funding);
+                                        *self.funding.lock().await = rest_funding;
+                                        *self.open_position.lock().await = rest;
+                                        let _ = info_sender.send(MarketCommand::ReceiveTrade(trade_info)).await;
+                                        let _ = info_sender.send(MarketCommand::UpdatePosition(self.position_snapshot().await)).await;
+                                        info!("Trade Closed: {:?}", trade_info);
+                                    },
+                                    Err(e) => {
+                                        warn!("Failed to close {} position, keeping it: {}", self.asset, e);
+                                        *self.open_position.lock().await = Some(open_pos);
+                                    },
                             };
                         };
                 },
@@ -321,9 +430,10 @@ impl Executor {
 
                             if let Some(trade_info) = self.cancel_trade().await{
                                     let _ = info_sender.send(MarketCommand::ReceiveTrade(trade_info)).await;
+                                    let _ = info_sender.send(MarketCommand::UpdatePosition(self.position_snapshot().await)).await;
                             };
 
-                        return;
+                        break;
 
                     },
                     
@@ -336,12 +446,16 @@ impl Executor {
                             if let Some(open_pos) = maybe_open{
                                 let liq_fill: TradeFillInfo = liq_fill.into();
                                 println!("MAKE SURE SIZES ARE THE SAME: \nLocal {open_pos:?}\nLiquidation: {liq_fill:?}");
+                                //the position is gone by now, the cache holds its funding up to the last settlement
+                                let funding = *self.funding.lock().await;
                                 let trade_info = Self::get_trade_info(
                                                     open_pos,
                                                     liq_fill,
-                                                    &self.fees);
+                                                    &self.fees,
+                                                    funding);
                                 
                                     let _ = info_sender.send(MarketCommand::ReceiveTrade(trade_info)).await;
+                                    let _ = info_sender.send(MarketCommand::UpdatePosition(None)).await;
                                     info!("LIQUIDATION INFO: {:?}", trade_info);
                             }
                 },
@@ -350,6 +464,7 @@ impl Executor {
                         
                         if let Some(trade_info) = self.cancel_trade().await{
                             let _ = info_sender.send(MarketCommand::ReceiveTrade(trade_info)).await;
+                            let _ = info_sender.send(MarketCommand::UpdatePosition(self.position_snapshot().await)).await;
                         };                        
                         self.toggle_pause();
                         info!("Executor is now {}", if self.is_paused { "paused" } else { "resumed" });
@@ -358,6 +473,7 @@ impl Executor {
                     TradeCommand::Pause => {
                         if let Some(trade_info) = self.cancel_trade().await{
                             let _ = info_sender.send(MarketCommand::ReceiveTrade(trade_info)).await;
+                            let _ = info_sender.send(MarketCommand::UpdatePosition(self.position_snapshot().await)).await;
                         }; 
                         self.is_paused = true;
                 },
@@ -369,7 +485,9 @@ impl Executor {
                     
         }
 
-    }}
+    }
+            funding_task.abort();
+    }
 
 
 }
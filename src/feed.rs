@@ -0,0 +1,184 @@
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+use hyperliquid_rust_sdk::{Error, InfoClient};
+
+use crate::{Candle, TimeFrame, BASE_TF};
+use crate::helper::{candles_snapshot, get_time_now};
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DataQuality{
+    //bars in [from, to) were missed by the feed, `backfilled` of them were recovered
+    Gap{from: u64, to: u64, missing: u64, backfilled: u64},
+    BackfillFailed{from: u64, to: u64},
+    Feed{status: FeedStatus, last_update: u64},
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DataQualityEvent{
+    pub asset: String,
+    pub timestamp: u64,
+    pub issue: DataQuality,
+}
+
+impl DataQualityEvent{
+    pub fn new(asset: String, issue: DataQuality) -> Self{
+        Self{
+            asset,
+            timestamp: get_time_now(),
+            issue,
+        }
+    }
+}
+
+
+//Watches the open times of the candle stream for bars that never arrived (e.g. while the ws reconnected)
+#[derive(Debug, Clone, Copy)]
+pub struct GapDetector{
+    tf: TimeFrame,
+    last_open: Option<u64>,
+}
+
+impl GapDetector{
+    pub fn new(tf: TimeFrame) -> Self{
+        Self{ tf, last_open: None }
+    }
+
+    //range to refetch, starts at the last bar seen since its final update was likely missed too
+    pub fn check(&mut self, open_time: u64) -> Option<(u64, u64)>{
+        let last = match self.last_open{
+            Some(last) => last,
+            None => {
+                self.last_open = Some(open_time);
+                return None;
+            },
+        };
+        if open_time <= last{
+            return None;
+        }
+        self.last_open = Some(open_time);
+
+        if open_time > last + self.tf.to_millis(){
+            Some((last, open_time))
+        }else{
+            None
+        }
+    }
+
+    pub fn missing(&self, (from, to): (u64, u64)) -> u64{
+        ((to - from) / self.tf.to_millis()).saturating_sub(1)
+    }
+}
+
+
+//candles with open time in [from, to), in order
+pub async fn backfill(info_client: &InfoClient, coin: &str, tf: TimeFrame, (from, to): (u64, u64)) -> Result<Vec<Candle>, Error>{
+    let mut candles = candles_snapshot(info_client, coin, tf, from, to.saturating_sub(1)).await?;
+    candles.retain(|c| c.open_time >= from && c.open_time < to);
+    candles.sort_by_key(|c| c.open_time);
+    Ok(candles)
+}
+
+
+//fetches a gap and reports it, returns the candles to replay (empty on failure)
+pub async fn recover_gap(info_client: &InfoClient, coin: &str, detector: &GapDetector, gap: (u64, u64)) -> (Vec<Candle>, DataQuality){
+    let missing = detector.missing(gap);
+    match backfill(info_client, coin, detector.tf, gap).await{
+        Ok(candles) => {
+            let backfilled = candles.iter().filter(|c| c.open_time > gap.0).count() as u64;
+            info!("{}: backfilled {}/{} missed {} bar(s)", coin, backfilled, missing, detector.tf.as_str());
+            (candles, DataQuality::Gap{from: gap.0, to: gap.1, missing, backfilled})
+        },
+        Err(e) => {
+            warn!("{}: failed to backfill missed bars: {:?}", coin, e);
+            (Vec::new(), DataQuality::BackfillFailed{from: gap.0, to: gap.1})
+        },
+    }
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum FeedStatus{
+    Live,
+    Stale,
+    EntriesPaused,
+    Flattened,
+}
+
+//Thresholds are in bars of the market's timeframe, never shorter than two base bars
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WatchdogConfig{
+    pub warn_after: f64,
+    pub pause_after: f64,
+    pub flatten_after: Option<f64>,
+}
+
+impl Default for WatchdogConfig{
+    fn default() -> Self{
+        Self{
+            warn_after: 1.0,
+            pause_after: 2.0,
+            flatten_after: None,
+        }
+    }
+}
+
+
+//Escalates Live -> Stale -> EntriesPaused -> Flattened while no candle arrives
+#[derive(Debug, Clone, Copy)]
+pub struct Watchdog{
+    cfg: WatchdogConfig,
+    tf: TimeFrame,
+    status: FeedStatus,
+}
+
+impl Watchdog{
+    pub fn new(tf: TimeFrame, cfg: WatchdogConfig) -> Self{
+        Self{
+            cfg,
+            tf,
+            status: FeedStatus::Live,
+        }
+    }
+
+    pub fn get_status(&self) -> FeedStatus{
+        self.status
+    }
+
+    pub fn update_config(&mut self, cfg: WatchdogConfig){
+        self.cfg = cfg;
+    }
+
+    pub fn set_time_frame(&mut self, tf: TimeFrame){
+        self.tf = tf;
+    }
+
+    fn threshold(&self, bars: f64) -> u64{
+        let min = 2 * BASE_TF.to_millis();
+        ((bars * self.tf.to_millis() as f64) as u64).max(min)
+    }
+
+    //returns the new status when it changes
+    pub fn check(&mut self, since_last: u64) -> Option<FeedStatus>{
+        let status = if self.cfg.flatten_after.is_some_and(|bars| since_last >= self.threshold(bars)){
+            FeedStatus::Flattened
+        }else if since_last >= self.threshold(self.cfg.pause_after){
+            FeedStatus::EntriesPaused
+        }else if since_last >= self.threshold(self.cfg.warn_after){
+            FeedStatus::Stale
+        }else{
+            FeedStatus::Live
+        };
+
+        if status == self.status{
+            return None;
+        }
+        self.status = status;
+        Some(status)
+    }
+}
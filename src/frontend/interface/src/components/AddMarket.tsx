@@ -1,5 +1,5 @@
 import React, { useState, useMemo } from 'react';
-import { into, TIMEFRAME_CAMELCASE, indicatorLabels, indicatorColors } from '../types';
+import { into, TIMEFRAME_CAMELCASE, indicatorLabels, indicatorColors, kindKey, sourceLabels, transformLabels } from '../types';
 import type {
   TimeFrame,
   Risk,
@@ -16,7 +16,7 @@ import type {
 const riskOptions: Risk[] = ['Low', 'Normal', 'High'];
 const styleOptions: Style[] = ['Scalp', 'Swing'];
 const stanceOptions: Stance[] = ['Bull', 'Bear', 'Neutral'];
-const indicatorKinds: IndicatorKind[] = ['rsi', 'smaOnRsi', 'stochRsi', 'adx', 'atr', 'ema', 'emaCross', 'sma'];
+const indicatorKinds: IndicatorKind[] = ['rsi', 'smaOnRsi', 'stochRsi', 'adx', 'atr', 'ema', 'emaCross', 'sma', 'macd', 'bollinger', 'keltner', 'donchian', 'vwap', 'anchoredVwap', 'obv', 'volumeSma', 'volumeEma', 'supertrend', 'psar', 'ichimoku', 'pipeline'];
 
 export const AddMarket: React.FC<AddMarketProps> = ({ onClose, totalMargin }) => {
   const [asset, setAsset] = useState('');
@@ -37,6 +37,8 @@ export const AddMarket: React.FC<AddMarketProps> = ({ onClose, totalMargin }) =>
   const [newParam, setNewParam] = useState(14);
   const [newParam2, setNewParam2] = useState(14);
   const [newTf, setNewTf] = useState<keyof typeof TIMEFRAME_CAMELCASE>('1m');
+  const [pipeSource, setPipeSource] = useState('atr');
+  const [pipeTransform, setPipeTransform] = useState('ema');
 
   const computedAmount = useMemo(
     () => (marginType === 'alloc' ? totalMargin * (marginValue / 100) : 0),
@@ -59,6 +61,38 @@ export const AddMarket: React.FC<AddMarketProps> = ({ onClose, totalMargin }) =>
       case 'adx':
         cfg = { adx: { periods: newParam, di_length: newParam2 } };
         break;
+      case 'macd':
+        cfg = { macd: { fast: newParam, slow: newParam2, signal: 9 } };
+        break;
+      case 'bollinger':
+        cfg = { bollinger: { periods: newParam, stdDev: 2.0 } };
+        break;
+      case 'keltner':
+        cfg = { keltner: { periods: newParam, atrPeriods: newParam2, multiplier: 2.0 } };
+        break;
+      case 'supertrend':
+        cfg = { supertrend: { atrPeriods: newParam, multiplier: 3.0 } };
+        break;
+      case 'psar':
+        cfg = { psar: { step: 0.02, max: 0.2 } };
+        break;
+      case 'ichimoku':
+        cfg = { ichimoku: { tenkan: 9, kijun: 26, senkou: 52 } };
+        break;
+      case 'pipeline': {
+        const source = ['close', 'hl2', 'volume', 'obv', 'vwap'].includes(pipeSource) ? pipeSource
+          : pipeSource === 'macdHistogram' ? { macdHistogram: { fast: 12, slow: 26, signal: 9 } }
+          : { [pipeSource]: newParam };
+        cfg = { pipeline: { source, transform: { [pipeTransform]: newParam2 } } };
+        break;
+      }
+      case 'vwap':
+      case 'obv':
+        cfg = newKind;
+        break;
+      case 'anchoredVwap':
+        cfg = { anchoredVwap: Date.now() - newParam * 60 * 60 * 1000 };
+        break;
       default:
         cfg = { [newKind]: newParam };
     }
@@ -173,7 +207,7 @@ export const AddMarket: React.FC<AddMarketProps> = ({ onClose, totalMargin }) =>
           <legend className="text-lg text-white">Indicators</legend>
           <div className="flex flex-col gap-2">
             {config.map(([ind, tf], i) => {
-              const kind = Object.keys(ind)[0] as IndicatorKind;
+              const kind = kindKey(ind);
               return (
                 <div key={i} className="flex items-center gap-2">
                   <span className={`${indicatorColors[kind]} px-3 py-1 rounded-full text-xs`}>{indicatorLabels[kind] || kind} -- {tf}</span>
@@ -189,7 +223,17 @@ export const AddMarket: React.FC<AddMarketProps> = ({ onClose, totalMargin }) =>
               <select value={newKind} onChange={e => setNewKind(e.target.value as IndicatorKind)} className={selectClass}>
                 {indicatorKinds.map(k => <option key={k} value={k}>{indicatorLabels[k]}</option>)}
               </select>
-              <div className="mt-2 grid grid-cols-2 gap-2">{(['emaCross','smaOnRsi','adx'].includes(newKind) ?
+              {newKind === 'pipeline' && (
+                <div className="mt-2 grid grid-cols-2 gap-2">
+                  <select value={pipeSource} onChange={e => setPipeSource(e.target.value)} className={selectClass}>
+                    {Object.keys(sourceLabels).map(k => <option key={k} value={k}>{sourceLabels[k]}</option>)}
+                  </select>
+                  <select value={pipeTransform} onChange={e => setPipeTransform(e.target.value)} className={selectClass}>
+                    {Object.keys(transformLabels).map(k => <option key={k} value={k}>{transformLabels[k]}</option>)}
+                  </select>
+                </div>
+              )}
+              <div className="mt-2 grid grid-cols-2 gap-2">{(['emaCross','smaOnRsi','adx','macd','keltner','pipeline'].includes(newKind) ?
                 <> <input type="number" value={newParam} onChange={e => setNewParam(+e.target.value)} placeholder="Param1" className={inputClass} />
                       <input type="number" value={newParam2} onChange={e => setNewParam2(+e.target.value)} placeholder="Param2" className={inputClass} /> </> :
                 <input type="number" value={newParam} onChange={e => setNewParam(+e.target.value)} className={inputClass} />)}</div>
@@ -1,13 +1,14 @@
 import React from 'react';
 import { motion } from 'framer-motion';
-import { Pause, Play, Trash2 } from 'lucide-react';
+import { Pause, Play, Trash2, X } from 'lucide-react';
 import type { MarketInfo } from '../types';
-import { indicatorLabels, indicatorColors, decompose, get_value, fromTimeFrame } from '../types';
+import { indicatorLabels, indicatorColors, decompose, get_value, fromTimeFrame, voterLabel, kindKey, describeDataQuality } from '../types';
 
 interface MarketCardProps {
   market: MarketInfo;
   onTogglePause: (asset: string) => void;
   onRemove: (asset: string) => void;
+  onDetachShadow: (name: string) => void;
 }
 
 const formatPrice = (n: number) => (n < 1 ? n.toFixed(4) : n.toFixed(2));
@@ -25,10 +26,13 @@ const PnlBar: React.FC<{ pnl: number }> = ({ pnl }) => {
   );
 };
 
-const MarketCard: React.FC<MarketCardProps> = ({ market, onTogglePause, onRemove }) => {
-  const { asset, price, lev, margin, params, pnl, is_paused, indicators } = market;
+const MarketCard: React.FC<MarketCardProps> = ({ market, onTogglePause, onRemove, onDetachShadow }) => {
+  const { asset, price, lev, margin, params, pnl, is_paused, indicators, votes, regime, signals, dataQuality, shadows } = market;
+  const lastSignal = signals && signals.length > 0 ? signals[signals.length - 1] : null;
+  const lastIssue = dataQuality && dataQuality.length > 0 ? dataQuality[dataQuality.length - 1] : null;
   const { strategy } = params;
-  const { risk, style, stance } = strategy.custom;
+  const { risk, style, stance } = 'custom' in strategy ? strategy.custom : strategy.ensemble;
+  const followTrend = 'custom' in strategy ? strategy.custom.followTrend : false;
 
   return (
     <motion.div whileHover={{ y: -2 }} className="group rounded-md border border-white/10 bg-[#111316] p-4 shadow-[0_2px_0_rgba(255,255,255,0.03),_0_12px_24px_rgba(0,0,0,0.35)]">
@@ -39,6 +43,7 @@ const MarketCard: React.FC<MarketCardProps> = ({ market, onTogglePause, onRemove
           <div className="-mt-0.5 flex items-baseline gap-3">
             <h2 className="text-3xl font-semibold tracking-tight">{asset}</h2>
             <span className={`relative bottom-1 rounded-md px-2 py-0.5 text-[10px] uppercase ${is_paused ? 'border border-amber-400/60 text-amber-300' : 'border border-orange-500/60 text-orange-300'}`}>{is_paused ? 'Paused' : 'Live'}</span>
+            {regime && <span className="relative bottom-1 rounded-md border border-white/20 px-2 py-0.5 text-[10px] uppercase text-white/70">{regime}</span>}
           </div>
           <div className="mt-1 font-mono text-sm text-white/70">${formatPrice(price)} • {lev}×</div>
         </div>
@@ -78,15 +83,68 @@ const MarketCard: React.FC<MarketCardProps> = ({ market, onTogglePause, onRemove
       <div className="mt-3 flex flex-wrap gap-2">
         {indicators.map((data, i) => {
           const { kind, timeframe, value } = decompose(data);
-          const kindKey = Object.keys(kind)[0] as keyof typeof indicatorColors;
+          const key = kindKey(kind);
           return (
-            <span key={i} title={get_value(value)} className={`rounded-md border border-white/10 bg-white/5 px-2.5 py-1 text-[11px] ${indicatorColors[kindKey]}`}>
-              {indicatorLabels[kindKey] || (kindKey as string)} — {fromTimeFrame(timeframe)}
+            <span key={i} title={get_value(value)} className={`rounded-md border border-white/10 bg-white/5 px-2.5 py-1 text-[11px] ${indicatorColors[key]}`}>
+              {indicatorLabels[key] || key} — {fromTimeFrame(timeframe)}
             </span>
           );
         })}
       </div>
 
+      {/* Votes */}
+      {votes && votes.length > 0 && (
+        <div className="mt-3 flex flex-wrap gap-2">
+          {votes.map((v, i) => (
+            <span key={i} title={`weight ${v.weight}`} className={`rounded-md border border-white/10 bg-white/5 px-2.5 py-1 font-mono text-[11px] ${v.score == null ? 'text-white/40' : v.score >= 0 ? 'text-orange-300' : 'text-rose-300'}`}>
+              {voterLabel(v.voter)} {v.score == null ? '—' : v.score.toFixed(2)}
+            </span>
+          ))}
+        </div>
+      )}
+
+      {/* Last signal */}
+      {lastSignal && (
+        <div className="mt-3 font-mono text-[11px] text-white/60">
+          Last signal: {Object.keys(lastSignal.command)[0]} @ {formatPrice(lastSignal.price)} — {typeof lastSignal.status === 'string' ? lastSignal.status : `skipped (${lastSignal.status.skipped})`}
+        </div>
+      )}
+
+      {/* Data quality */}
+      {lastIssue && (
+        <div className="mt-2 font-mono text-[11px] text-amber-300/80" title={new Date(lastIssue.timestamp).toLocaleString()}>
+          {describeDataQuality(lastIssue.issue)}
+        </div>
+      )}
+
+      {/* Shadows: live pnl over the same window next to each paper engine */}
+      {shadows && shadows.length > 0 && (
+        <div className="mt-4 border-t border-white/10 pt-3">
+          <div className="text-[10px] uppercase text-white/50">Live vs Shadow</div>
+          <div className="mt-1 grid gap-1 font-mono text-[11px] tabular-nums">
+            {shadows.map(s => {
+              const live = pnl - s.livePnlAtAttach;
+              const delta = s.pnl - live;
+              return (
+                <div key={s.name} className={`flex items-center justify-between gap-2 ${s.isActive ? 'text-white/80' : 'text-white/40'}`}>
+                  <span className="truncate" title={`${'custom' in s.params.strategy ? 'custom' : 'ensemble'} • ${s.trades} trade(s), ${s.trades > 0 ? ((100 * s.wins) / s.trades).toFixed(0) : '0'}% win`}>
+                    {s.name}{s.inPosition ? ' •' : ''}
+                  </span>
+                  <span className={live >= 0 ? 'text-orange-300' : 'text-rose-300'}>{live >= 0 ? '+' : ''}{live.toFixed(2)}</span>
+                  <span className={s.pnl >= 0 ? 'text-orange-300' : 'text-rose-300'}>{s.pnl >= 0 ? '+' : ''}{s.pnl.toFixed(2)}</span>
+                  <span className="text-white/50">Δ {delta >= 0 ? '+' : ''}{delta.toFixed(2)}</span>
+                  {s.isActive && (
+                    <button onClick={() => onDetachShadow(s.name)} className="rounded-md p-0.5 hover:bg-white/10" title="Detach">
+                      <X className="h-3 w-3 text-white/50" />
+                    </button>
+                  )}
+                </div>
+              );
+            })}
+          </div>
+        </div>
+      )}
+
       {/* Strategy */}
       <div className="mt-4 grid grid-cols-3 gap-3 border-t border-white/10 pt-3 text-xs">
         <div>
@@ -99,7 +157,7 @@ const MarketCard: React.FC<MarketCardProps> = ({ market, onTogglePause, onRemove
         </div>
         <div className="text-right">
           <div className="text-[10px] uppercase text-white/50">Trend Following</div>
-          <div className="text-white/90">{strategy.custom.followTrend ? 'Yes' : 'No'}</div>
+          <div className="text-white/90">{followTrend ? 'Yes' : 'No'}</div>
         </div>
       </div>
     </motion.div>
@@ -3,7 +3,7 @@ import { motion, AnimatePresence } from 'framer-motion';
 import { Plus, Power, Pause, X, AlertCircle } from 'lucide-react';
 import MarketCard from './MarketCard';
 import { AddMarket } from './AddMarket';
-import type { MarketInfo, Message, assetPrice, MarketTradeInfo, assetMargin, indicatorData } from '../types';
+import type { MarketInfo, Message, assetPrice, MarketTradeInfo, assetMargin, indicatorData, Vote, Regime, MarketSignalEvent, DataQualityEvent, ShadowInfo } from '../types';
 
 export default function MarketsPage() {
   const [errorMsg, setErrorMsg] = useState<string | null>(null);
@@ -44,6 +44,21 @@ export default function MarketsPage() {
         } else if ('updateIndicatorValues' in payload) {
           const { asset, data } = payload.updateIndicatorValues as { asset: string; data: indicatorData[] };
           setMarkets(prev => prev.map(m => (m.asset === asset ? { ...m, indicators: data } : m)));
+        } else if ('updateStrategyVotes' in payload) {
+          const { asset, votes } = payload.updateStrategyVotes as { asset: string; votes: Vote[] };
+          setMarkets(prev => prev.map(m => (m.asset === asset ? { ...m, votes } : m)));
+        } else if ('updateRegime' in payload) {
+          const { asset, regime } = payload.updateRegime as { asset: string; regime: Regime };
+          setMarkets(prev => prev.map(m => (m.asset === asset ? { ...m, regime } : m)));
+        } else if ('newSignalEvent' in payload) {
+          const { asset, event } = payload.newSignalEvent as MarketSignalEvent;
+          setMarkets(prev => prev.map(m => (m.asset === asset ? { ...m, signals: [...(m.signals ?? []), event].slice(-50) } : m)));
+        } else if ('dataQuality' in payload) {
+          const event = payload.dataQuality as DataQualityEvent;
+          setMarkets(prev => prev.map(m => (m.asset === event.asset ? { ...m, dataQuality: [...(m.dataQuality ?? []), event].slice(-20) } : m)));
+        } else if ('shadowUpdate' in payload) {
+          const shadow = payload.shadowUpdate as ShadowInfo;
+          setMarkets(prev => prev.map(m => (m.asset === shadow.asset ? { ...m, shadows: [...(m.shadows ?? []).filter(s => s.name !== shadow.name), shadow] } : m)));
         } else if ('userError' in payload) {
           setErrorMsg(payload.userError);
           if (errRef.current) clearTimeout(errRef.current);
@@ -76,6 +91,12 @@ export default function MarketsPage() {
       body: JSON.stringify({ toggleMarket: asset.toUpperCase() }),
     });
   };
+  const detach_shadow = async (asset: string, name: string) => {
+    await fetch('http://localhost:8090/command', {
+      method: 'POST', headers: { 'Content-Type': 'application/json' },
+      body: JSON.stringify({ marketComm: { asset: asset.toUpperCase(), cmd: { detachShadow: name } } }),
+    });
+  };
   const load_session = async () => {
     await fetch('http://localhost:8090/command', {
       method: 'POST', headers: { 'Content-Type': 'application/json' },
@@ -178,7 +199,7 @@ export default function MarketsPage() {
             <div className="grid grid-cols-1 gap-7 sm:grid-cols-2 xl:grid-cols-3">
               {markets.map(m => (
                 <motion.div key={m.asset} initial={{ opacity: 0, y: 10 }} animate={{ opacity: 1, y: 0 }}>
-                  <MarketCard market={m} onTogglePause={() => handleConfirmToggle(m.asset, m.is_paused)} onRemove={() => setMarketToRemove(m.asset)} />
+                  <MarketCard market={m} onTogglePause={() => handleConfirmToggle(m.asset, m.is_paused)} onRemove={() => setMarketToRemove(m.asset)} onDetachShadow={name => detach_shadow(m.asset, name)} />
                 </motion.div>
               ))}
             </div>
@@ -6,7 +6,74 @@ export type IndicatorKind =
   | { atr: number }
   | { ema: number }
   | { emaCross: { short: number; long: number } }
-  | { sma: number };
+  | { sma: number }
+  | { macd: { fast: number; slow: number; signal: number } }
+  | { bollinger: { periods: number; stdDev: number } }
+  | { keltner: { periods: number; atrPeriods: number; multiplier: number } }
+  | { donchian: number }
+  | "vwap"
+  | { anchoredVwap: number }
+  | "obv"
+  | { volumeSma: number }
+  | { volumeEma: number }
+  | { supertrend: { atrPeriods: number; multiplier: number } }
+  | { psar: { step: number; max: number } }
+  | { ichimoku: { tenkan: number; kijun: number; senkou: number } }
+  | { pipeline: { source: Source; transform: Transform } };
+
+export type Source =
+  | "close"
+  | "hl2"
+  | "volume"
+  | { rsi: number }
+  | { atr: number }
+  | { adx: number }
+  | { ema: number }
+  | { sma: number }
+  | "obv"
+  | "vwap"
+  | { macdHistogram: { fast: number; slow: number; signal: number } };
+
+export type Transform =
+  | { ema: number }
+  | { sma: number }
+  | { rsi: number }
+  | { zScore: number };
+
+export const sourceLabels: Record<string, string> = {
+  close: 'Close',
+  hl2: 'HL2',
+  volume: 'Volume',
+  rsi: 'RSI',
+  atr: 'ATR',
+  adx: 'ADX',
+  ema: 'EMA',
+  sma: 'SMA',
+  obv: 'OBV',
+  vwap: 'VWAP',
+  macdHistogram: 'MACD Hist',
+};
+
+export const transformLabels: Record<string, string> = {
+  ema: 'EMA',
+  sma: 'SMA',
+  rsi: 'RSI',
+  zScore: 'Z-Score',
+};
+
+export function pipelineLabel(source: Source, transform: Transform): string {
+  const key = (o: Source | Transform) => typeof o === 'string' ? o : Object.keys(o)[0];
+  const arg = (o: Source | Transform) => {
+    if (typeof o === 'string') return '';
+    const v = Object.values(o)[0];
+    return typeof v === 'number' ? `(${v})` : '';
+  };
+  return `${transformLabels[key(transform)]}${arg(transform)} of ${sourceLabels[key(source)]}${arg(source)}`;
+}
+
+export function kindKey(kind: IndicatorKind): string {
+  return typeof kind === 'string' ? kind : Object.keys(kind)[0];
+}
 
 export interface MarketInfo{
     asset: string, 
@@ -17,9 +84,28 @@ export interface MarketInfo{
     pnl: number,
     is_paused: boolean,
     indicators: indicatorData[],
+    votes?: Vote[],
+    regime?: Regime,
+    signals?: SignalEvent[],
+    dataQuality?: DataQualityEvent[],
+    shadows?: ShadowInfo[],
     trades: TradeInfo[],
 }
 
+export interface ShadowInfo {
+    asset: string,
+    name: string,
+    params: TradeParams,
+    startMargin: number,
+    margin: number,
+    pnl: number,
+    livePnlAtAttach: number,
+    trades: number,
+    wins: number,
+    inPosition: boolean,
+    isActive: boolean,
+}
+
 export interface indicatorData {
     id: IndexId,
     value?: Value
@@ -34,6 +120,18 @@ export type Value =
   | { smaRsiValue: number }
   | { adxValue: number }
   | { atrValue: number }
+  | { macdValue: { macd: number; signal: number; histogram: number } }
+  | { bollingerValue: { upper: number; mid: number; lower: number } }
+  | { keltnerValue: { upper: number; mid: number; lower: number } }
+  | { donchianValue: { upper: number; mid: number; lower: number } }
+  | { vwapValue: number }
+  | { anchoredVwapValue: number }
+  | { obvValue: number }
+  | { volumeMaValue: { volume: number; average: number } }
+  | { supertrendValue: { level: number; trend: boolean } }
+  | { psarValue: { level: number; trend: boolean } }
+  | { ichimokuValue: { tenkan: number; kijun: number; spanA: number; spanB: number; trend?: boolean | null } }
+  | { pipelineValue: { source: Source; transform: Transform; value: number } }
 
 export function get_value(v?: Value): string {
   if (!v) return "No value";
@@ -45,6 +143,18 @@ export function get_value(v?: Value): string {
   if ("smaRsiValue" in v) return `SMA on RSI: ${v.smaRsiValue.toFixed(2)}`;
   if ("adxValue" in v) return `ADX: ${v.adxValue.toFixed(2)}`;
   if ("atrValue" in v) return `ATR: ${v.atrValue.toFixed(2)}`;
+  if ("macdValue" in v) return `MACD: ${v.macdValue.macd.toFixed(2)}, signal=${v.macdValue.signal.toFixed(2)}, hist=${v.macdValue.histogram.toFixed(2)}`;
+  if ("bollingerValue" in v) return `BB: ${v.bollingerValue.lower.toFixed(2)} / ${v.bollingerValue.mid.toFixed(2)} / ${v.bollingerValue.upper.toFixed(2)}`;
+  if ("keltnerValue" in v) return `Keltner: ${v.keltnerValue.lower.toFixed(2)} / ${v.keltnerValue.mid.toFixed(2)} / ${v.keltnerValue.upper.toFixed(2)}`;
+  if ("donchianValue" in v) return `Donchian: ${v.donchianValue.lower.toFixed(2)} / ${v.donchianValue.mid.toFixed(2)} / ${v.donchianValue.upper.toFixed(2)}`;
+  if ("vwapValue" in v) return `VWAP: ${v.vwapValue.toFixed(2)}`;
+  if ("anchoredVwapValue" in v) return `Anchored VWAP: ${v.anchoredVwapValue.toFixed(2)}`;
+  if ("obvValue" in v) return `OBV: ${v.obvValue.toFixed(0)}`;
+  if ("volumeMaValue" in v) return `Volume: ${v.volumeMaValue.volume.toFixed(2)}, avg=${v.volumeMaValue.average.toFixed(2)}`;
+  if ("supertrendValue" in v) return `Supertrend: ${v.supertrendValue.level.toFixed(2)} ${v.supertrendValue.trend ? "↑" : "↓"}`;
+  if ("psarValue" in v) return `PSAR: ${v.psarValue.level.toFixed(2)} ${v.psarValue.trend ? "↑" : "↓"}`;
+  if ("ichimokuValue" in v) return `Ichimoku: tenkan=${v.ichimokuValue.tenkan.toFixed(2)}, kijun=${v.ichimokuValue.kijun.toFixed(2)}, cloud=${v.ichimokuValue.spanA.toFixed(2)}/${v.ichimokuValue.spanB.toFixed(2)} ${v.ichimokuValue.trend == null ? "in cloud" : v.ichimokuValue.trend ? "↑" : "↓"}`;
+  if ("pipelineValue" in v) return `${pipelineLabel(v.pipelineValue.source, v.pipelineValue.transform)}: ${v.pipelineValue.value.toFixed(2)}`;
   return "Unknown";
 }
 
@@ -116,20 +226,88 @@ export type Risk = "Low" | "Normal" | "High";
 export type Style = "Scalp" | "Swing";
 export type Stance = "Bull" | "Bear" | "Neutral";
 
+export type Regime = "trending" | "ranging" | "volatile";
+
+export interface RegimeSetting {
+  risk: Risk;
+  style: Style;
+  stance: Stance;
+}
+
+export interface RegimeParams {
+  trending: RegimeSetting;
+  ranging: RegimeSetting;
+  volatile: RegimeSetting;
+}
+
 export interface CustomStrategy {
   risk: Risk;
   style: Style;
   stance: Stance;
   followTrend: boolean;
+  regimes?: RegimeParams | null;
 }
 
-export type Strategy = { custom: CustomStrategy };
+export type Voter =
+  | "rsiScalp"
+  | "emaTrend"
+  | "adxBreakout"
+  | { pipeline: { source: Source; transform: Transform; low: number; high: number } };
+
+export interface WeightedVoter {
+  voter: Voter;
+  weight: number;
+}
+
+export interface EnsembleStrategy {
+  risk: Risk;
+  style: Style;
+  stance: Stance;
+  voters: WeightedVoter[];
+  threshold: number;
+}
+
+export interface Vote {
+  voter: Voter;
+  weight: number;
+  score?: number | null;
+}
+
+export const voterLabels: Record<string, string> = {
+  rsiScalp: 'RSI Scalp',
+  emaTrend: 'EMA Trend',
+  adxBreakout: 'ADX Breakout',
+};
+
+export function voterLabel(voter: Voter): string {
+  if (typeof voter === 'string') return voterLabels[voter];
+  return pipelineLabel(voter.pipeline.source, voter.pipeline.transform);
+}
+
+export type Strategy =
+  | { custom: CustomStrategy }
+  | { ensemble: EnsembleStrategy };
+
+export interface SignalFilter {
+  exitCooldown: number;
+  lossCooldown: number;
+  sameSideCooldown: number;
+  evalOnClose?: TimeFrame | null;
+}
 
 export interface TradeParams {
   timeFrame: TimeFrame;  
   lev: number;
   strategy: Strategy;
   tradeTime: number;
+  signalFilter?: SignalFilter;
+  watchdog?: WatchdogConfig;
+}
+
+export interface WatchdogConfig {
+  warnAfter: number;
+  pauseAfter: number;
+  flattenAfter?: number | null;
 }
 
 export type MarginAllocation =
@@ -153,6 +331,11 @@ export type Message =
     | { updateTotalMargin: number}
     | { updateMarketMargin: assetMargin }
     | { updateIndicatorValues: {asset: string, data: indicatorData[] }}
+    | { updateStrategyVotes: {asset: string, votes: Vote[] }}
+    | { updateRegime: {asset: string, regime: Regime }}
+    | { newSignalEvent: MarketSignalEvent }
+    | { dataQuality: DataQualityEvent }
+    | { shadowUpdate: ShadowInfo }
     | { marketInfoEdit: [string, editMarketInfo]}
     | { userError: string }
     | { loadSession: MarketInfo[]};
@@ -173,12 +356,38 @@ export interface TradeInfo{
     close: number,
     pnl: number,
     fee: number,
+    funding?: number,
     is_long: number,
     duration?: number,
-    oid: [number, number]
+    oid: [number, number],
+    regime?: Regime | null,
 };
 
 
+export type TradeCommand =
+    | { executeTrade: { size: number; isLong: boolean; duration: number } }
+    | { openTrade: { size: number; isLong: boolean } }
+    | { closeTrade: { size: number } }
+    | { buildPosition: { size: number; isLong: boolean; interval: number } };
+
+export type SkipReason = "cooldown" | "positionOpen" | "executorBusy" | "feedStale";
+
+export type SignalStatus = "accepted" | { skipped: SkipReason };
+
+export interface SignalEvent {
+    timestamp: number,
+    strategy: string,
+    indicators: indicatorData[],
+    price: number,
+    command: TradeCommand,
+    status: SignalStatus,
+}
+
+export interface MarketSignalEvent {
+    asset: string,
+    event: SignalEvent,
+}
+
 export interface MarketTradeInfo{
     asset: string,
     info: TradeInfo,
@@ -194,6 +403,19 @@ export const indicatorLabels: Record<string, string> = {
   ema: 'EMA',
   emaCross: 'EMA Cross',
   sma: 'SMA',
+  macd: 'MACD',
+  bollinger: 'Bollinger Bands',
+  keltner: 'Keltner Channels',
+  donchian: 'Donchian Channels',
+  vwap: 'VWAP',
+  anchoredVwap: 'Anchored VWAP',
+  obv: 'OBV',
+  volumeSma: 'Volume SMA',
+  volumeEma: 'Volume EMA',
+  supertrend: 'Supertrend',
+  psar: 'Parabolic SAR',
+  ichimoku: 'Ichimoku',
+  pipeline: 'Pipeline',
 };
 
 export const indicatorColors: Record<string, string> = {
@@ -205,13 +427,44 @@ export const indicatorColors: Record<string, string> = {
   ema: 'bg-blue-800 text-blue-200',
   emaCross: 'bg-pink-800 text-pink-200',
   sma: 'bg-gray-800 text-gray-200',
+  macd: 'bg-cyan-800 text-cyan-200',
+  bollinger: 'bg-teal-800 text-teal-200',
+  keltner: 'bg-lime-800 text-lime-200',
+  donchian: 'bg-orange-800 text-orange-200',
+  vwap: 'bg-amber-800 text-amber-200',
+  anchoredVwap: 'bg-amber-900 text-amber-200',
+  obv: 'bg-sky-800 text-sky-200',
+  volumeSma: 'bg-slate-700 text-slate-200',
+  volumeEma: 'bg-slate-800 text-slate-200',
+  supertrend: 'bg-emerald-800 text-emerald-200',
+  psar: 'bg-fuchsia-800 text-fuchsia-200',
+  ichimoku: 'bg-violet-800 text-violet-200',
+  pipeline: 'bg-rose-800 text-rose-200',
 };
 
 
+export type DataQuality =
+  | { gap: { from: number; to: number; missing: number; backfilled: number } }
+  | { backfillFailed: { from: number; to: number } }
+  | { feed: { status: FeedStatus; lastUpdate: number } };
 
+export type FeedStatus = "live" | "stale" | "entriesPaused" | "flattened";
 
+const feedStatusLabels: Record<FeedStatus, string> = {
+  live: 'Feed live',
+  stale: 'Feed stale',
+  entriesPaused: 'Feed stale: entries paused',
+  flattened: 'Feed stale: position flattened',
+};
 
+export interface DataQualityEvent {
+    asset: string,
+    timestamp: number,
+    issue: DataQuality,
+}
 
-
-
-
+export function describeDataQuality(issue: DataQuality): string {
+  if ('gap' in issue) return `Feed gap: ${issue.gap.backfilled}/${issue.gap.missing} bars backfilled`;
+  if ('backfillFailed' in issue) return `Feed gap since ${new Date(issue.backfillFailed.from).toLocaleTimeString()}: backfill failed`;
+  return `${feedStatusLabels[issue.feed.status]} (last candle ${new Date(issue.feed.lastUpdate).toLocaleTimeString()})`;
+}
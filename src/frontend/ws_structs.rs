@@ -1,9 +1,12 @@
 use serde::{Deserialize, Serialize};
 use crate::{MarketTradeInfo,MarginAllocation, IndexId, TradeParams, Value, AssetPrice, AssetMargin};
+use crate::strategy::Vote;
+use crate::signal::{Regime, MarketSignalEvent};
+use crate::{DataQualityEvent, ShadowInfo};
 use std::collections::HashMap;
 
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct AddMarketInfo {
     pub asset: String,
@@ -52,6 +55,11 @@ pub enum UpdateFrontend{
     UpdateTotalMargin(f64),
     UpdateMarketMargin(AssetMargin),
     UpdateIndicatorValues{asset: String, data: Vec<IndicatorData>},
+    UpdateStrategyVotes{asset: String, votes: Vec<Vote>},
+    UpdateRegime{asset: String, regime: Regime},
+    NewSignalEvent(MarketSignalEvent),
+    DataQuality(DataQualityEvent),
+    ShadowUpdate(ShadowInfo),
     MarketInfoEdit((String, EditMarketInfo)),
     UserError(String),
     LoadSession(Vec<MarketInfo>),
@@ -0,0 +1,104 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use hyperliquid_rust_sdk::{Error, InfoClient};
+
+use crate::helper::get_time_now;
+
+
+//Hyperliquid settles funding every hour on the oracle price
+pub const FUNDING_INTERVAL_MS: u64 = 60 * 60 * 1000;
+//max rows returned by one fundingHistory request
+const PAGE_SIZE: usize = 500;
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FundingRate{
+    pub time: u64,
+    //hourly rate, longs pay shorts when positive
+    pub rate: f64,
+}
+
+
+//Hourly funding rates of one asset, cheap to clone
+#[derive(Debug, Clone, Default)]
+pub struct FundingSchedule{
+    rates: Arc<BTreeMap<u64, f64>>,
+}
+
+
+impl FundingSchedule{
+
+    pub fn new(rates: Vec<FundingRate>) -> Self{
+        //settlements land a few ms after the hour, key them by the hour they belong to
+        let rates = rates.into_iter()
+            .map(|r| ((r.time + FUNDING_INTERVAL_MS / 2) / FUNDING_INTERVAL_MS * FUNDING_INTERVAL_MS, r.rate))
+            .collect();
+        Self{
+            rates: Arc::new(rates),
+        }
+    }
+
+    pub async fn fetch(info_client: &InfoClient, coin: &str, start: u64, end: u64) -> Result<Self, Error>{
+        Ok(Self::new(funding_history(info_client, coin, start, end).await?))
+    }
+
+    pub fn is_empty(&self) -> bool{
+        self.rates.is_empty()
+    }
+
+    //last known rate at `time`, 0 before the first one
+    pub fn rate_at(&self, time: u64) -> f64{
+        self.rates.range(..=time).next_back().map_or(0.0, |(_, r)| *r)
+    }
+
+    //funding received by a position over the settlements in (from, to], negative when paid
+    pub fn payment(&self, is_long: bool, size: f64, price: f64, from: u64, to: u64) -> f64{
+        if self.rates.is_empty(){
+            return 0.0;
+        }
+        let side = if is_long {-1.0} else {1.0};
+        let mut hour = (from / FUNDING_INTERVAL_MS + 1) * FUNDING_INTERVAL_MS;
+        let mut total = 0.0;
+        while hour <= to{
+            total += side * size * price * self.rate_at(hour);
+            hour += FUNDING_INTERVAL_MS;
+        }
+        total
+    }
+
+    pub fn rates(&self) -> Vec<FundingRate>{
+        self.rates.iter().map(|(time, rate)| FundingRate{time: *time, rate: *rate}).collect()
+    }
+}
+
+
+//paged, the endpoint only returns PAGE_SIZE rows per call
+pub async fn funding_history(info_client: &InfoClient, coin: &str, start: u64, end: u64) -> Result<Vec<FundingRate>, Error>{
+    let mut rates = Vec::new();
+    let mut from = start;
+    while from < end{
+        let page = info_client.funding_history(coin.to_string(), from, Some(end)).await?;
+        let len = page.len();
+        for entry in page{
+            let rate = entry.funding_rate.parse::<f64>()
+                .map_err(|e| Error::GenericParse(format!("failed to parse funding rate, {}", e)))?;
+            rates.push(FundingRate{time: entry.time, rate});
+        }
+        match rates.last(){
+            Some(last) if len >= PAGE_SIZE => from = last.time + 1,
+            _ => break,
+        }
+    }
+    Ok(rates)
+}
+
+
+pub async fn latest_funding(info_client: &InfoClient, coin: &str) -> Result<Option<FundingRate>, Error>{
+    let now = get_time_now();
+    let rates = funding_history(info_client, coin, now - 2 * FUNDING_INTERVAL_MS, now).await?;
+    Ok(rates.last().copied())
+}
@@ -2,7 +2,7 @@ use log::info;
 use hyperliquid_rust_sdk::{Error,AssetMeta, InfoClient, Message, Subscription};
 use tokio::sync::mpsc::{UnboundedReceiver};
 use std::time::{SystemTime, UNIX_EPOCH};
-use kwant::indicators::{Price};
+use crate::Candle;
 use ethers::types::H160;
 use crate::TimeFrame;
 use log::warn;
@@ -48,13 +48,13 @@ fn get_time_now_and_candles_ago(candle_count: u64, tf: TimeFrame) -> (u64, u64)
 
 
 
-async fn candles_snapshot(info_client: &InfoClient,coin: &str,time_frame: TimeFrame, start: u64, end: u64) -> Result<Vec<Price>, Error>{
+pub async fn candles_snapshot(info_client: &InfoClient,coin: &str,time_frame: TimeFrame, start: u64, end: u64) -> Result<Vec<Candle>, Error>{
  
     let vec = info_client
     .candles_snapshot(coin.to_string(), time_frame.to_string(), start, end)
     .await?;
 
-    let mut res: Vec<Price> = Vec::with_capacity(vec.len());
+    let mut res: Vec<Candle> = Vec::with_capacity(vec.len());
     for candle in vec {
         let h = candle.high.parse::<f64>()
                 .map_err(|e| Error::GenericParse(format!("Failed to parse high: {}", e)))?;
@@ -64,19 +64,24 @@ async fn candles_snapshot(info_client: &InfoClient,coin: &str,time_frame: TimeFr
                 .map_err(|e| Error::GenericParse(format!("Failed to parse open: {}", e)))?;
         let c = candle.close.parse::<f64>()
                 .map_err(|e| Error::GenericParse(format!("Failed to parse close: {}", e)))?;
+        let v = candle.vlm.parse::<f64>()
+                .map_err(|e| Error::GenericParse(format!("Failed to parse volume: {}", e)))?;
 
-        res.push(Price {
+        res.push(Candle {
+            open_time: candle.time_open,
             high: h,
             low: l,
             open: o,
             close: c,
+            volume: v,
+            trades: candle.num_trades,
     });
     }
     Ok(res)
 }
 
 
-pub async fn load_candles(info_client: &InfoClient,coin: &str,tf: TimeFrame, candle_count: u64) -> Result<Vec<Price>, Error> {
+pub async fn load_candles(info_client: &InfoClient,coin: &str,tf: TimeFrame, candle_count: u64) -> Result<Vec<Candle>, Error> {
 
 
     let (start, end) = get_time_now_and_candles_ago(candle_count + 1, tf);
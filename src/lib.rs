@@ -3,7 +3,15 @@ mod executor;
 mod consts;
 mod assets;
 mod wallet;
-mod backtest; 
+pub mod backtest; 
+mod candle;
+mod feed;
+mod store;
+mod recorder;
+mod funding;
+mod liquidation;
+mod paper;
+mod shadow;
 
 
 pub mod frontend;
@@ -17,16 +25,24 @@ pub mod margin;
 pub use frontend::*;
 pub use bot::{Bot, BotEvent, BotToMarket};
 pub use wallet::Wallet;
-pub use signal::{SignalEngine, IndexId, IndicatorKind, EditType, Entry};
+pub use signal::{SignalEngine, IndexId, IndicatorKind, EditType, Entry, Regime, SignalFilter, SignalEvent, SignalStatus, Factor, Source, Transform};
 pub use market::{Market, MarketCommand, MarketUpdate, AssetPrice};
-pub use consts::{MAX_HISTORY};
+pub use consts::{MAX_HISTORY, BASE_TF};
 pub use assets::MARKETS;
 pub use executor::Executor;
-// pub use backtest::BackTester; 
-pub use trade_setup::{TradeParams, TimeFrame, TradeCommand, TradeInfo, MarketTradeInfo, TradeFillInfo, LiquidationFillInfo};
+pub use candle::{Candle, CandleAggregator};
+pub use store::{CandleStore, STORE_DIR};
+pub use recorder::{Recorder, Replayer, ReplaySpeed, ReplayOutcome, Record};
+pub use feed::{DataQuality, DataQualityEvent, FeedStatus, WatchdogConfig};
+pub use liquidation::{MarginTable, MarginTier};
+pub use paper::PaperExecutor;
+pub use shadow::{ShadowConfig, ShadowInfo};
+pub use funding::{FundingRate, FundingSchedule, FUNDING_INTERVAL_MS, funding_history, latest_funding};
+pub use backtest::BackTester; 
+pub use trade_setup::{TradeParams, TimeFrame, TradeCommand, TradeInfo, MarketTradeInfo, TradeFillInfo, LiquidationFillInfo, PositionSnapshot};
 pub use margin::{AssetMargin, MarginAllocation};
 
 //expost HL sdk types
 pub use hyperliquid_rust_sdk::{BaseUrl, Error};
 pub use ethers::signers::LocalWallet;
-pub use kwant::indicators::Value;
+pub use signal::Value;
@@ -0,0 +1,78 @@
+use serde::{Deserialize, Serialize};
+
+
+//Notional at and above `lower_bound` can use at most `max_lev`
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MarginTier{
+    pub lower_bound: f64,
+    pub max_lev: u32,
+}
+
+
+//Hyperliquid margin table: maintenance margin is half the initial margin at the tier's max leverage,
+//with a deduction per tier so the requirement stays continuous across tier bounds
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MarginTable{
+    tiers: Vec<MarginTier>,
+}
+
+
+impl MarginTable{
+
+    pub fn new(mut tiers: Vec<MarginTier>) -> Self{
+        tiers.sort_by(|a, b| a.lower_bound.total_cmp(&b.lower_bound));
+        if tiers.first().is_none_or(|t| t.lower_bound > 0.0){
+            let max_lev = tiers.first().map_or(1, |t| t.max_lev);
+            tiers.insert(0, MarginTier{lower_bound: 0.0, max_lev});
+        }
+        Self{tiers}
+    }
+
+    pub fn flat(max_lev: u32) -> Self{
+        Self::new(vec![MarginTier{lower_bound: 0.0, max_lev}])
+    }
+
+    fn tier(&self, notional: f64) -> usize{
+        self.tiers.iter().rposition(|t| t.lower_bound <= notional).unwrap_or(0)
+    }
+
+    fn rate(tier: &MarginTier) -> f64{
+        1.0 / (2.0 * tier.max_lev.max(1) as f64)
+    }
+
+    pub fn max_lev(&self, notional: f64) -> u32{
+        self.tiers[self.tier(notional)].max_lev
+    }
+
+    //(maintenance rate, deduction) of the tier `notional` falls in
+    fn maintenance(&self, notional: f64) -> (f64, f64){
+        let tier = self.tier(notional);
+        let mut deduction = 0.0;
+        for i in 1..=tier{
+            deduction += self.tiers[i].lower_bound * (Self::rate(&self.tiers[i]) - Self::rate(&self.tiers[i - 1]));
+        }
+        (Self::rate(&self.tiers[tier]), deduction)
+    }
+
+    pub fn maintenance_margin(&self, notional: f64) -> f64{
+        let (rate, deduction) = self.maintenance(notional);
+        (notional * rate - deduction).max(0.0)
+    }
+
+    //price at which `margin` + upnl falls to the maintenance margin of an isolated position,
+    //the tier is picked from the entry notional
+    pub fn liquidation_price(&self, is_long: bool, size: f64, entry: f64, margin: f64) -> f64{
+        if size <= 0.0{
+            return if is_long {0.0} else {f64::INFINITY};
+        }
+        let (rate, deduction) = self.maintenance(size * entry);
+        let price = if is_long{
+            (size * entry - margin - deduction) / (size * (1.0 - rate))
+        }else{
+            (size * entry + margin + deduction) / (size * (1.0 + rate))
+        };
+        price.max(0.0)
+    }
+}
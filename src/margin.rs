@@ -18,7 +18,8 @@ pub enum MarginAllocation{
 pub type MarginMap = HashMap<String, f64, BuildHasherDefault<FxHasher>>;
 
 pub struct MarginBook{
-    user: Arc<Wallet>,
+    //None for a simulated book, its total only moves with apply_pnl
+    user: Option<Arc<Wallet>>,
     map: MarginMap,
     pub total_on_chain: f64,
 }
@@ -29,21 +30,44 @@ impl MarginBook{
     pub fn new(user: Arc<Wallet>) -> Self{
         
         Self{
-            user,
+            user: Some(user),
             map: HashMap::default(),
             total_on_chain: f64::from_bits(1),
         }
     }
+
+    pub fn simulated(total: f64) -> Self{
+        Self{
+            user: None,
+            map: HashMap::default(),
+            total_on_chain: total,
+        }
+    }
+
     pub async fn sync(&mut self) -> Result<(), Error>{
-        self.total_on_chain = self.user.get_user_margin().await?;
+        if let Some(user) = &self.user{
+            self.total_on_chain = user.get_user_margin().await?;
+        }
         Ok(())
     }
 
+    //realized pnl of a simulated book, a live one picks it up on the next sync
+    pub fn apply_pnl(&mut self, pnl: f64){
+        if self.user.is_none(){
+            self.total_on_chain += pnl;
+        }
+    }
+
+    pub fn get(&self, asset: &str) -> Option<f64>{
+        self.map.get(asset).copied()
+    }
+
 
     pub async fn update_asset(&mut self, update: AssetMargin) -> Result<f64, Error>{
         let (asset, requested_margin) = update;
         self.sync().await?;
-        let free = self.free();
+        //the asset's current allocation is replaced, so it counts as free
+        let free = self.free() + self.get(&asset).unwrap_or(0.0);
          
         if requested_margin > free{
             return Err(Error::InsufficientFreeMargin(free));
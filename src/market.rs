@@ -1,26 +1,33 @@
 #![allow(unused_variables)]
 use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
-use log::info;
+use std::sync::atomic::{AtomicU64, Ordering};
+use log::{info, warn};
 use serde::Deserialize;
 
 
 use ethers::signers::LocalWallet;
+use ethers::types::H160;
 use hyperliquid_rust_sdk::{AssetMeta,Error, BaseUrl, ExchangeClient, InfoClient, Message};
 
-use kwant::indicators::Price;
 
-use crate::{MAX_HISTORY, MarketInfo, MarketTradeInfo};
+use crate::{MAX_HISTORY, BASE_TF, MarketInfo, MarketTradeInfo, Candle};
 
 use crate::executor::Executor;
-use crate::signal::{SignalEngine, ExecParam, EngineCommand, TimeFrameData, Entry, EditType, IndexId};
-use crate::trade_setup::{TimeFrame, TradeParams, TradeCommand, TradeInfo, LiquidationFillInfo};
-use crate::strategy::Strategy;
-use crate::helper::load_candles;
+use crate::signal::{SignalEngine, ExecParam, EngineCommand, TimeFrameData, Entry, EditType, IndexId, Regime, SignalFilter, SignalEvent, MarketSignalEvent};
+use crate::trade_setup::{TimeFrame, TradeParams, TradeCommand, TradeInfo, LiquidationFillInfo, PositionSnapshot};
+use crate::strategy::{Strategy, Vote};
+use crate::helper::{get_time_now, load_candles};
+use crate::store::{CandleStore, STORE_DIR};
+use crate::backtest::{Report, REPORT_DIR};
+use crate::feed::{GapDetector, DataQuality, DataQualityEvent, FeedStatus, Watchdog, recover_gap};
 use crate::{IndicatorData,AssetMargin, UpdateFrontend};
+use crate::shadow::{self, ShadowConfig, ShadowHandle};
+use crate::recorder::Recorder;
 
 use tokio::{
-    sync::mpsc::{channel, Sender, Receiver, UnboundedSender, UnboundedReceiver, unbounded_channel},
+    sync::{Mutex, mpsc::{channel, Sender, Receiver, UnboundedSender, UnboundedReceiver, unbounded_channel}},
+    time::{interval, Duration},
 };
 
 use flume::{bounded, Sender as FlumeSender};
@@ -31,9 +38,14 @@ use flume::{bounded, Sender as FlumeSender};
 
 
 pub struct Market {
-    info_client: InfoClient, 
+    info_client: Arc<InfoClient>,
+    feed_client: InfoClient,
+    store: CandleStore,
     exchange_client: ExchangeClient,
     pub trade_history: Vec<TradeInfo>,
+    //(time, margin) at start and after every closed trade, feeds the performance report. Net of
+    //manual margin changes: each trade scales it by its return, a top-up doesn't move it
+    pub margin_history: Vec<(u64, f64)>,
     pub pnl: f64,
     pub trade_params: TradeParams,
     pub asset: AssetMeta,
@@ -43,6 +55,13 @@ pub struct Market {
     senders: MarketSenders,
     pub active_tfs: HashSet<TimeFrame>,
     pub margin: f64,
+    pub regime: Option<Regime>,
+    entry_regime: Option<Regime>,
+    fees: (f64, f64),
+    //engines trading on paper off this market's feed
+    shadows: Arc<Mutex<Vec<ShadowHandle>>>,
+    //gets every snapshot loaded into the engine when the bot records
+    recorder: Option<Recorder>,
 }
 
 
@@ -51,6 +70,7 @@ impl Market{
 
     pub async fn new(
                     wallet: LocalWallet,
+                    user: H160,
                     url: BaseUrl,
                     bot_tx: UnboundedSender<MarketUpdate>,
                     price_rv: UnboundedReceiver<Message>,
@@ -61,7 +81,8 @@ impl Market{
                     config: Option<Vec<IndexId>>
     ) -> Result<(Self, Sender<MarketCommand>), Error>{
 
-        let mut info_client = InfoClient::new(None, Some(url)).await?;
+        let info_client = Arc::new(InfoClient::new(None, Some(url)).await?);
+        let feed_client = InfoClient::new(None, Some(url)).await?;
         let exchange_client = ExchangeClient::new(None, wallet.clone(), Some(url), None, None).await?;
         
         //Look up needed tfs for loading 
@@ -83,6 +104,7 @@ impl Market{
             bot_tx,
             engine_tx,
             exec_tx: exec_tx.clone(), 
+            market_tx: market_tx.clone(),
         };
 
         let receivers = MarketReceivers{
@@ -92,17 +114,25 @@ impl Market{
 
         Ok((Market{ 
             info_client,
+            feed_client,
+            store: CandleStore::new(STORE_DIR),
             exchange_client,
             margin,
             trade_history: Vec::with_capacity(MAX_HISTORY),
+            margin_history: vec![(get_time_now(), margin)],
             pnl: 0_f64,
             trade_params : trade_params.clone(),
             asset: asset.clone(), 
             signal_engine: SignalEngine::new(config, trade_params,engine_rv, Some(market_tx.clone()),exec_tx, margin).await,
-            executor: Executor::new(wallet, asset.name, fees,exec_rv ,market_tx.clone()).await?,
+            executor: Executor::new(wallet, user, asset.name, fees,exec_rv ,market_tx.clone()).await?,
             receivers,
             senders,
             active_tfs,
+            regime: None,
+            entry_regime: None,
+            fees,
+            shadows: Arc::new(Mutex::new(Vec::new())),
+            recorder: None,
         }, market_tx,
         ))
     }
@@ -124,6 +154,10 @@ impl Market{
 
 
 
+    pub fn set_recorder(&mut self, recorder: Recorder){
+        self.recorder = Some(recorder);
+    }
+
     pub fn change_strategy(&mut self, strategy: Strategy){
 
         self.trade_params.strategy = strategy;
@@ -134,10 +168,13 @@ impl Market{
         
         info!("---------Loading Engine: this may take some time----------------");
         for tf in &self.active_tfs{
-            let price_data = load_candles(&self.info_client, 
+            let price_data = self.store.warm_up(&self.info_client, 
                                          self.asset.name.as_str(),
                                          *tf,
                                         candle_count).await?;
+            if let Some(recorder) = &self.recorder{
+                recorder.record_warm_up(&self.asset.name, *tf, &price_data);
+            }
             self.signal_engine.load(*tf, price_data).await;
             }
 
@@ -149,6 +186,11 @@ impl Market{
 
         &self.trade_history
     }
+
+    pub fn report(&self) -> Report{
+        let start_margin = self.margin_history.first().map_or(self.margin, |m| m.1);
+        Report::from_history(&self.asset.name, &self.trade_history, start_margin, &self.margin_history)
+    }
     
 }
 
@@ -178,7 +220,7 @@ impl Market{
             signal_engine.start().await;
         });
         //Start exucutor
-        let executor_handle = tokio::spawn(async move {
+        let mut executor_handle = tokio::spawn(async move {
             executor.start().await;
         });
         //Candle Stream
@@ -186,16 +228,49 @@ impl Market{
         let bot_price_update = self.senders.bot_tx.clone();
 
         let asset_name: Arc<str> = Arc::from(self.asset.name.clone());
+        let feed_client = self.feed_client;
+        let feed_tf = BASE_TF;
+        let stream_shadows = self.shadows.clone();
+        let last_update = Arc::new(AtomicU64::new(get_time_now()));
+        let stream_last_update = last_update.clone();
+        //kept current by the command loop for the watchdog
+        let position = Arc::new(Mutex::new(None::<PositionSnapshot>));
+        let time_frame = Arc::new(Mutex::new(self.trade_params.time_frame));
         let candle_stream_handle = tokio::spawn(async move {
                 let mut curr = f64::from_bits(1);
+                let mut gaps = GapDetector::new(feed_tf);
                 while let Some(Message::Candle(candle)) = self.receivers.price_rv.recv().await{
+                    stream_last_update.store(get_time_now(), Ordering::Relaxed);
                     let close = candle.data.close.parse::<f64>().ok().unwrap();
                     let high = candle.data.high.parse::<f64>().ok().unwrap();
                     let low = candle.data.low.parse::<f64>().ok().unwrap();            
                     let open = candle.data.open.parse::<f64>().ok().unwrap();
-                    let price = Price{open,high, low, close};
+                    let volume = candle.data.volume.parse::<f64>().unwrap_or(0.0);
+                    let price = Candle{
+                        open_time: candle.data.time_open,
+                        open,
+                        high,
+                        low,
+                        close,
+                        volume,
+                        trades: candle.data.num_trades,
+                    };
+
+                    //bars skipped by the feed (reconnects) are fetched and replayed before the new one
+                    if let Some(gap) = gaps.check(price.open_time){
+                        let (candles, issue) = recover_gap(&feed_client, &asset_name, &gaps, gap).await;
+                        let mut shadows = stream_shadows.lock().await;
+                        for c in candles{
+                            let _ = engine_price_tx.send(EngineCommand::UpdatePrice(c));
+                            shadows.retain(|s| s.feed.send(c).is_ok());
+                        }
+                        let _ = bot_price_update.send(MarketUpdate::RelayToFrontend(
+                            UpdateFrontend::DataQuality(DataQualityEvent::new(asset_name.to_string(), issue))
+                        ));
+                    }
                      
                     let _ = engine_price_tx.send(EngineCommand::UpdatePrice(price));
+                    stream_shadows.lock().await.retain(|s| s.feed.send(price).is_ok());
                     if close != curr {
                         let _ = bot_price_update.send(MarketUpdate::PriceUpdate((asset_name.clone().to_string(), close)));
                         curr = close;
@@ -203,10 +278,54 @@ impl Market{
             }
         });
 
+        //Stale feed watchdog
+        let watchdog_engine_tx = self.senders.engine_tx.clone();
+        let watchdog_exec_tx = self.senders.exec_tx.clone();
+        let watchdog_bot_tx = self.senders.bot_tx.clone();
+        let watchdog_asset = self.asset.name.clone();
+        let watchdog_position = position.clone();
+        let watchdog_tf = time_frame.clone();
+        let mut watchdog = Watchdog::new(self.trade_params.time_frame, self.trade_params.watchdog);
+        let watchdog_handle = tokio::spawn(async move {
+            let mut ticker = interval(Duration::from_secs(5));
+            while !watchdog_engine_tx.is_closed(){
+                ticker.tick().await;
+                let last = last_update.load(Ordering::Relaxed);
+                watchdog.set_time_frame(*watchdog_tf.lock().await);
+                let prev = watchdog.get_status();
+                let Some(status) = watchdog.check(get_time_now().saturating_sub(last)) else { continue };
+
+                match status{
+                    FeedStatus::Live => info!("{}: candle feed recovered", watchdog_asset),
+                    FeedStatus::Stale => warn!("{}: no candle since {}", watchdog_asset, last),
+                    FeedStatus::EntriesPaused => warn!("{}: feed stale, pausing entries", watchdog_asset),
+                    FeedStatus::Flattened => {
+                        warn!("{}: feed stale, flattening position", watchdog_asset);
+                        let open = *watchdog_position.lock().await;
+                        if let Some(pos) = open{
+                            let _ = watchdog_exec_tx.send_async(TradeCommand::CloseTrade{size: pos.size}).await;
+                        }
+                    },
+                }
+                let paused = matches!(status, FeedStatus::EntriesPaused | FeedStatus::Flattened);
+                if paused != matches!(prev, FeedStatus::EntriesPaused | FeedStatus::Flattened){
+                    let _ = watchdog_engine_tx.send(EngineCommand::PauseEntries(paused));
+                }
+                let _ = watchdog_bot_tx.send(MarketUpdate::RelayToFrontend(
+                    UpdateFrontend::DataQuality(DataQualityEvent::new(
+                        watchdog_asset.clone(),
+                        DataQuality::Feed{status, last_update: last},
+                    ))
+                ));
+            }
+        });
+
         //listen to changes and trade results
         let engine_update_tx = self.senders.engine_tx.clone();
+        let market_update_tx = self.senders.market_tx.clone();
         let bot_update_tx = self.senders.bot_tx;
         let asset = self.asset.clone();
+        let mut executor_done = false;
 
 
 
@@ -228,10 +347,13 @@ impl Market{
                         let mut map: TimeFrameData = HashMap::new(); 
                         for &entry in &entry_vec{
                             if entry.edit == EditType::Add && !self.active_tfs.contains(&entry.id.1){
-                                let tf_data = load_candles(&self.info_client,
+                                let tf_data = self.store.warm_up(&self.info_client,
                                                             asset.name.as_str(),
                                                             entry.id.1,
                                                             3000).await?;
+                                if let Some(recorder) = &self.recorder{
+                                    recorder.record_warm_up(&asset.name, entry.id.1, &tf_data);
+                                }
                                 map.insert(entry.id.1, tf_data);
                                 self.active_tfs.insert(entry.id.1);    
                             }
@@ -243,10 +365,13 @@ impl Market{
                                                                                     });
                     },
                     
-                    MarketCommand::ReceiveTrade(trade_info) =>{
+                    MarketCommand::ReceiveTrade(mut trade_info) =>{
+                        trade_info.regime = self.entry_regime.take().or(self.regime);
                         self.pnl += trade_info.pnl;
                         self.margin += trade_info.pnl;
                         self.trade_history.push(trade_info);
+                        push_margin(&mut self.margin_history, self.margin, trade_info.pnl);
+                        let _ = engine_update_tx.send(EngineCommand::ReceiveTrade(trade_info));
                         let _ = engine_update_tx.send(EngineCommand::UpdateExecParams(ExecParam::Margin(self.margin)));
                         let _ = bot_update_tx.send(MarketUpdate::TradeUpdate(
                             MarketTradeInfo{
@@ -257,15 +382,84 @@ impl Market{
                         let _ = bot_update_tx.send(MarketUpdate::MarginUpdate((asset.name.clone(), self.margin)));
                     },
 
+                    MarketCommand::UpdatePosition(pos) =>{
+                        if pos.is_some(){
+                            self.entry_regime = self.regime;
+                        }
+                        *position.lock().await = pos;
+                        let _ = engine_update_tx.send(EngineCommand::UpdatePosition(pos));
+                    },
+
+                    MarketCommand::ReceiveSignal(event) =>{
+                        let _ = bot_update_tx.send(
+                            MarketUpdate::RelayToFrontend(
+                                UpdateFrontend::NewSignalEvent(
+                                    MarketSignalEvent{
+                                        asset: asset.name.to_string(),
+                                        event,
+                                })
+                        ));
+                    },
+
+                    MarketCommand::StoreCandles(bars) =>{
+                        let store = self.store.clone();
+                        let asset = asset.name.clone();
+                        tokio::spawn(async move{
+                            for (tf, bar) in bars{
+                                if let Err(e) = store.append(&asset, tf, &[bar]).await{
+                                    warn!("Failed to store {} {} candle: {}", asset, tf.as_str(), e);
+                                }
+                            }
+                        });
+                    },
+
+                    MarketCommand::ReloadTracker(tf) =>{
+                        //straight from the exchange, the store has the same hole
+                        let info_client = self.info_client.clone();
+                        let engine_tx = engine_update_tx.clone();
+                        let asset = asset.name.clone();
+                        let recorder = self.recorder.clone();
+                        tokio::spawn(async move{
+                            match load_candles(&info_client, &asset, tf, 2000).await{
+                                Ok(price_data) => {
+                                    if let Some(recorder) = &recorder{
+                                        recorder.record_warm_up(&asset, tf, &price_data);
+                                    }
+                                    let _ = engine_tx.send(EngineCommand::ReloadTracker{tf, price_data});
+                                },
+                                Err(e) => warn!("Failed to reload {} {} after a gap: {}", asset, tf.as_str(), e),
+                            }
+                        });
+                    },
+
+                    MarketCommand::UpdateRegime(regime) =>{
+                        info!("{} regime: {:?}", asset.name, regime);
+                        self.regime = Some(regime);
+                        let _ = bot_update_tx.send(
+                            MarketUpdate::RelayToFrontend(
+                                UpdateFrontend::UpdateRegime{
+                                    asset: asset.name.to_string(),
+                                    regime,
+                            })
+                        );
+                    },
+
                     MarketCommand::ReceiveLiquidation(liq_fill) => {
                        self.senders.exec_tx.send_async(TradeCommand::Liquidation(liq_fill)).await;
                     },
 
                     MarketCommand::UpdateTimeFrame(tf)=>{
                         self.trade_params.time_frame = tf;
+                        *time_frame.lock().await = tf;
                         let _ = engine_update_tx.send(EngineCommand::UpdateExecParams(ExecParam::Tf(tf)));
                     },
                     
+                    MarketCommand::UpdateSignalFilter(filter) => {
+                        self.trade_params.signal_filter = filter;
+                        let _ = engine_update_tx.send(EngineCommand::UpdateSignalFilter(filter));
+                    },
+
+                    //a cash flow, not a return: margin_history only moves with trades
                     MarketCommand::UpdateMargin(marge) => {
                         self.margin = marge;
                         let _ = engine_update_tx.send(EngineCommand::UpdateExecParams(ExecParam::Margin(self.margin)));
@@ -282,6 +476,70 @@ impl Market{
                     );
                     },
                     
+                    MarketCommand::UpdateVotes(votes) =>{
+                            let _ = bot_update_tx.send(
+                                MarketUpdate::RelayToFrontend(
+                                        UpdateFrontend::UpdateStrategyVotes{
+                                                asset: asset.name.to_string(),
+                                                votes,
+                                })
+                    );
+                    },
+                    
+                    MarketCommand::AttachShadow(cfg) =>{
+                        if self.shadows.lock().await.iter().any(|s| s.name == cfg.name){
+                            let _ = bot_update_tx.send(MarketUpdate::RelayToFrontend(UpdateFrontend::UserError(
+                                format!("{} already has a shadow named {}", asset.name, cfg.name)
+                            )));
+                            continue;
+                        }
+                        //the warm-up downloads candles, the handle comes back as ShadowReady
+                        let store = self.store.clone();
+                        let info_client = self.info_client.clone();
+                        let asset = asset.clone();
+                        let (margin, pnl, fees) = (self.margin, self.pnl, self.fees);
+                        let bot_tx = bot_update_tx.clone();
+                        let market_tx = market_update_tx.clone();
+                        tokio::spawn(async move{
+                            let mut price_data: TimeFrameData = HashMap::new();
+                            for tf in cfg.time_frames(){
+                                match store.warm_up(&info_client, asset.name.as_str(), tf, 2000).await{
+                                    Ok(data) => {price_data.insert(tf, data);},
+                                    Err(e) => {
+                                        warn!("Failed to load {} {} for shadow {}: {}", asset.name, tf.as_str(), cfg.name, e);
+                                        break;
+                                    },
+                                }
+                            }
+                            if price_data.len() < cfg.time_frames().len(){
+                                let _ = bot_tx.send(MarketUpdate::RelayToFrontend(UpdateFrontend::UserError(
+                                    format!("Failed to load candles for shadow {} on {}", cfg.name, asset.name)
+                                )));
+                                return;
+                            }
+                            let handle = shadow::spawn(&asset, cfg, margin, pnl, fees, price_data, bot_tx).await;
+                            //market closed meanwhile: dropping the handle detaches the shadow
+                            let _ = market_tx.send(MarketCommand::ShadowReady(handle)).await;
+                        });
+                    },
+
+                    MarketCommand::ShadowReady(handle) =>{
+                        let mut shadows = self.shadows.lock().await;
+                        //two attaches with one name raced through their warm-ups, the first one stays
+                        if shadows.iter().any(|s| s.name == handle.name){
+                            let _ = bot_update_tx.send(MarketUpdate::RelayToFrontend(UpdateFrontend::UserError(
+                                format!("{} already has a shadow named {}", asset.name, handle.name)
+                            )));
+                            continue;
+                        }
+                        shadows.push(handle);
+                    },
+
+                    MarketCommand::DetachShadow(name) =>{
+                        //the shadow flattens and reports itself inactive once its feed closes
+                        self.shadows.lock().await.retain(|s| s.name != name);
+                    },
+
                     MarketCommand::Toggle =>{
                        let _ = self.senders.exec_tx.send_async(TradeCommand::Toggle).await;  
                     },
@@ -297,18 +555,35 @@ impl Market{
 
                     MarketCommand::Close=>{
                     info!("\nClosing {} Market...\n", asset.name);
+                    self.shadows.lock().await.clear();
                     let _ = engine_update_tx.send(EngineCommand::Stop);
                     //shutdown Executor
                     info!("\nShutting down executor\n");
                     match self.senders.exec_tx.send(TradeCommand::CancelTrade) {
                         Ok(_) =>{
-                            if let Some(cmd) = self.receivers.market_rv.recv().await {
+                            //signals and position updates can be queued ahead of the final trade,
+                            //the executor exits after reporting it or right away when flat
+                            loop{
+                                let cmd = if executor_done{
+                                    self.receivers.market_rv.try_recv().ok()
+                                }else{
+                                    tokio::select!{
+                                        biased;
+                                        cmd = self.receivers.market_rv.recv() => cmd,
+                                        _ = &mut executor_handle => {
+                                            executor_done = true;
+                                            continue;
+                                        },
+                                    }
+                                };
+                                let Some(cmd) = cmd else { break };
                                 match cmd {
                                     MarketCommand::ReceiveTrade(trade_info) => {
                                         info!("\nReceived final trade before shutdown: {:?}\n", trade_info);
                                         self.pnl += trade_info.pnl;
                                         self.margin += trade_info.pnl;
                                         self.trade_history.push(trade_info);
+                                        push_margin(&mut self.margin_history, self.margin, trade_info.pnl);
                                         let _ = bot_update_tx.send(MarketUpdate::TradeUpdate(
                                             MarketTradeInfo{
                                                 asset: asset.name.to_string(),
@@ -317,7 +592,9 @@ impl Market{
                                         break;
                                         },
 
-                                    _ => break,
+                                    MarketCommand::UpdatePosition(pos) => *position.lock().await = pos,
+
+                                    _ => {},
 
                                     }}
                             },
@@ -334,14 +611,32 @@ impl Market{
                 };
         
         let _ = engine_handle.await;
-        let _ = executor_handle.await;
+        if !executor_done{
+            let _ = executor_handle.await;
+        }
         let _ = candle_stream_handle.await;
+        let _ = watchdog_handle.await;
         info!("No. of trade : {}\nPNL: {}",&self.trade_history.len(),&self.pnl);
+        if !self.trade_history.is_empty(){
+            let name = format!("{}-{}", self.asset.name, get_time_now());
+            if let Err(e) = self.report().write(REPORT_DIR, &name){
+                warn!("Failed to write performance report for {}: {}", self.asset.name, e);
+            }
+        }
         Ok(())
     }
 }
 
 
+//appends the trade's return to the history, `margin` being the margin after a trade of `pnl`
+fn push_margin(history: &mut Vec<(u64, f64)>, margin: f64, pnl: f64){
+    let last = history.last().map_or(margin - pnl, |m| m.1);
+    let before = margin - pnl;
+    let value = if before > 0.0 {last * margin / before} else {last + pnl};
+    history.push((get_time_now(), value));
+}
+
+
 
 
 
@@ -354,10 +649,22 @@ pub enum MarketCommand{
     UpdateStrategy(Strategy),
     EditIndicators(Vec<Entry>),
     UpdateTimeFrame(TimeFrame),
+    UpdateSignalFilter(SignalFilter),
     ReceiveTrade(TradeInfo),
     ReceiveLiquidation(LiquidationFillInfo),
+    UpdatePosition(Option<PositionSnapshot>),
     UpdateMargin(f64),
     UpdateIndicatorData(Vec<IndicatorData>),
+    UpdateVotes(Vec<Vote>),
+    UpdateRegime(Regime),
+    ReceiveSignal(SignalEvent),
+    StoreCandles(Vec<(TimeFrame, Candle)>),
+    ReloadTracker(TimeFrame),
+    AttachShadow(ShadowConfig),
+    //internal, a shadow done warming up
+    #[serde(skip)]
+    ShadowReady(ShadowHandle),
+    DetachShadow(String),
     Toggle,
     Resume,
     Pause,
@@ -369,6 +676,8 @@ struct MarketSenders{
     bot_tx: UnboundedSender<MarketUpdate>,
     engine_tx: UnboundedSender<EngineCommand>,
     exec_tx: FlumeSender<TradeCommand>,
+    //for tasks reporting back to the command loop
+    market_tx: Sender<MarketCommand>,
 }
 
 
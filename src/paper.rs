@@ -0,0 +1,304 @@
+use flume::Receiver;
+use log::{info, warn};
+use tokio::{
+    sync::mpsc::{Sender, UnboundedReceiver, unbounded_channel},
+    time::{interval, Duration},
+};
+
+use hyperliquid_rust_sdk::{BaseUrl, InfoClient};
+
+use crate::{Candle, MarginTable, FUNDING_INTERVAL_MS, latest_funding};
+use crate::helper::get_time_now;
+use crate::market::MarketCommand;
+use crate::trade_setup::{TradeCommand, TradeInfo, PositionSnapshot};
+
+
+#[derive(Debug, Clone, Copy)]
+struct PaperPosition{
+    is_long: bool,
+    size: f64,
+    entry: f64,
+    open_time: u64,
+    close_at: Option<u64>,
+    oid: u64,
+    //isolated margin backing the position
+    margin: f64,
+    funding: f64,
+}
+
+
+//Executor stand-in that fills at the last price instead of trading, reports like Executor does
+pub struct PaperExecutor{
+    trade_rv: Receiver<TradeCommand>,
+    price_rv: UnboundedReceiver<Candle>,
+    market_tx: Sender<MarketCommand>,
+    asset: String,
+    //taker rate
+    fee: f64,
+    lev: u32,
+    margin_table: MarginTable,
+    is_paused: bool,
+    position: Option<PaperPosition>,
+    last: Option<Candle>,
+    oid: u64,
+    clock: Option<u64>, //simulated time in replays, wall clock when None
+}
+
+
+impl PaperExecutor{
+
+    pub fn new(
+        asset: String,
+        fees: (f64, f64),
+        lev: u32,
+        max_lev: u32,
+        trade_rv: Receiver<TradeCommand>,
+        price_rv: UnboundedReceiver<Candle>,
+        market_tx: Sender<MarketCommand>,
+    ) -> Self{
+        Self{
+            trade_rv,
+            price_rv,
+            market_tx,
+            asset,
+            fee: fees.1,
+            lev: lev.min(max_lev).max(1),
+            margin_table: MarginTable::flat(max_lev),
+            is_paused: false,
+            position: None,
+            last: None,
+            oid: 0,
+            clock: None,
+        }
+    }
+
+    pub fn set_clock(&mut self, time: u64){
+        self.clock = Some(time);
+    }
+
+    fn now(&self) -> u64{
+        self.clock.unwrap_or_else(get_time_now)
+    }
+
+    async fn open(&mut self, size: f64, is_long: bool, close_at: Option<u64>){
+        if self.position.is_some() || self.is_paused{
+            return;
+        }
+        //nothing to fill at before the first candle
+        let Some(price) = self.last.map(|c| c.close) else { return };
+        if size <= 0.0{
+            return;
+        }
+        self.oid += 2;
+        let pos = PaperPosition{
+            is_long,
+            size,
+            entry: price,
+            open_time: self.now(),
+            close_at,
+            oid: self.oid,
+            margin: size * price / self.lev as f64,
+            funding: 0.0,
+        };
+        self.position = Some(pos);
+        let _ = self.market_tx.send(MarketCommand::UpdatePosition(self.snapshot())).await;
+    }
+
+    fn close(&mut self, size: f64) -> Option<TradeInfo>{
+        let pos = self.position?;
+        let price = self.last.map_or(pos.entry, |c| c.close);
+        let size = size.min(pos.size);
+        let fee = size * (pos.entry + price) * self.fee;
+        let gross = if pos.is_long {size * (price - pos.entry)} else {size * (pos.entry - price)};
+        let funding = pos.funding * size / pos.size;
+
+        let rest = pos.size - size;
+        self.position = (rest > 0.0).then_some(PaperPosition{
+            size: rest,
+            margin: pos.margin * rest / pos.size,
+            funding: pos.funding - funding,
+            ..pos
+        });
+
+        Some(TradeInfo{
+            open: pos.entry,
+            close: price,
+            pnl: gross - fee + funding,
+            fee,
+            funding,
+            is_long: pos.is_long,
+            duration: Some(self.now().saturating_sub(pos.open_time) / 1000),
+            oid: (pos.oid, pos.oid + 1),
+            regime: None,
+        })
+    }
+
+    //the whole isolated margin is lost with the funding it absorbed, like BackTester::liquidate
+    fn liquidate(&mut self, price: f64) -> Option<TradeInfo>{
+        let pos = self.position.take()?;
+        let fee = pos.size * pos.entry * self.fee;
+        info!("Paper {} position liquidated at {}", self.asset, price);
+        Some(TradeInfo{
+            open: pos.entry,
+            close: price,
+            pnl: -pos.margin - fee,
+            fee,
+            funding: 0.0,
+            is_long: pos.is_long,
+            duration: Some(self.now().saturating_sub(pos.open_time) / 1000),
+            oid: (pos.oid, pos.oid + 1),
+            regime: None,
+        })
+    }
+
+    fn snapshot(&self) -> Option<PositionSnapshot>{
+        self.position.map(|p| PositionSnapshot{
+            is_long: p.is_long,
+            size: p.size,
+            entry: p.entry,
+            upnl: 0.0,
+            open_time: p.open_time,
+        })
+    }
+
+    async fn report(&self, trade: Option<TradeInfo>){
+        if let Some(trade) = trade{
+            let _ = self.market_tx.send(MarketCommand::ReceiveTrade(trade)).await;
+            let _ = self.market_tx.send(MarketCommand::UpdatePosition(self.snapshot())).await;
+            info!("Paper trade closed on {}: {:?}", self.asset, trade);
+        }
+    }
+
+    pub async fn on_price(&mut self, candle: Candle){
+        self.last = Some(candle);
+        let Some(pos) = self.position else { return };
+
+        let liq = self.margin_table.liquidation_price(pos.is_long, pos.size, pos.entry, pos.margin + pos.funding);
+        if (pos.is_long && candle.low <= liq) || (!pos.is_long && candle.high >= liq){
+            let trade = self.liquidate(liq);
+            self.report(trade).await;
+            return;
+        }
+        if pos.close_at.is_some_and(|at| at <= self.now()){
+            let trade = self.close(pos.size);
+            self.report(trade).await;
+        }
+    }
+
+    //false once cancelled, the position is flattened by then
+    async fn on_trade(&mut self, cmd: TradeCommand) -> bool{
+        match cmd{
+            TradeCommand::ExecuteTrade{size, is_long, duration} => {
+                self.open(size, is_long, Some(self.now() + duration * 1000)).await;
+            },
+            TradeCommand::OpenTrade{size, is_long} => {
+                self.open(size, is_long, None).await;
+            },
+            TradeCommand::CloseTrade{size} => {
+                if !self.is_paused{
+                    let trade = self.close(size);
+                    self.report(trade).await;
+                }
+            },
+            TradeCommand::CancelTrade => {
+                self.flatten().await;
+                return false;
+            },
+            TradeCommand::Toggle | TradeCommand::Pause => {
+                let trade = self.close(f64::MAX);
+                self.report(trade).await;
+                self.is_paused = matches!(cmd, TradeCommand::Pause) || !self.is_paused;
+            },
+            TradeCommand::Resume => self.is_paused = false,
+            //liquidations of the live account don't concern paper positions,
+            //BuildPosition isn't implemented by the live Executor either
+            TradeCommand::Liquidation(_) | TradeCommand::BuildPosition{..} => {},
+        }
+        true
+    }
+
+    //fills the commands queued since the last call, replays drive the executor through it instead of start
+    pub async fn poll_trades(&mut self) -> bool{
+        while let Ok(cmd) = self.trade_rv.try_recv(){
+            if !self.on_trade(cmd).await{
+                return false;
+            }
+        }
+        true
+    }
+
+    //closes whatever is left at the last price, the trade is reported without a position update
+    pub async fn flatten(&mut self){
+        if let Some(trade) = self.close(f64::MAX){
+            let _ = self.market_tx.send(MarketCommand::ReceiveTrade(trade)).await;
+        }
+    }
+
+    fn settle_funding(&mut self, rate: f64){
+        let (Some(pos), Some(price)) = (self.position.as_mut(), self.last.map(|c| c.close)) else { return };
+        let side = if pos.is_long {-1.0} else {1.0};
+        pos.funding += side * pos.size * price * rate;
+    }
+
+    pub async fn start(mut self){
+        info!("PAPER EXECUTOR STARTED: {}", self.asset);
+        let info_client = match InfoClient::new(None, Some(BaseUrl::Mainnet)).await{
+            Ok(client) => Some(client),
+            Err(e) => {
+                warn!("Paper executor {}: no funding, {}", self.asset, e);
+                None
+            },
+        };
+        let mut settled = get_time_now() / FUNDING_INTERVAL_MS;
+        let mut ticker = interval(Duration::from_secs(60));
+
+        //the engine try_sends on a rendezvous channel like it does to Executor. Paper fills never keep
+        //the executor busy, so a receiver parked on its own task stands in for an idle live one,
+        //the select below is often handling a price when the engine sends
+        let (cmd_tx, mut cmd_rv) = unbounded_channel::<TradeCommand>();
+        let trade_rv = self.trade_rv.clone();
+        tokio::spawn(async move{
+            while let Ok(cmd) = trade_rv.recv_async().await{
+                if cmd_tx.send(cmd).is_err(){
+                    break;
+                }
+            }
+        });
+
+        loop{
+            tokio::select!{
+                cmd = cmd_rv.recv() => {
+                    let Some(cmd) = cmd else { break };
+                    if !self.on_trade(cmd).await{
+                        return;
+                    }
+                },
+
+                candle = self.price_rv.recv() => {
+                    let Some(candle) = candle else { break };
+                    self.on_price(candle).await;
+                },
+
+                _ = ticker.tick() => {
+                    let hour = get_time_now() / FUNDING_INTERVAL_MS;
+                    if hour <= settled{
+                        continue;
+                    }
+                    settled = hour;
+                    if self.position.is_none(){
+                        continue;
+                    }
+                    let Some(client) = &info_client else { continue };
+                    match latest_funding(client, &self.asset).await{
+                        Ok(Some(rate)) => self.settle_funding(rate.rate),
+                        Ok(None) => {},
+                        Err(e) => warn!("Paper executor {}: failed to fetch funding, {}", self.asset, e),
+                    }
+                },
+            }
+        }
+
+        //feed or engine gone, flatten like CancelTrade
+        self.flatten().await;
+    }
+}
@@ -0,0 +1,439 @@
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+use ethers::types::H160;
+use flate2::Compression;
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use flume::bounded;
+use futures_util::{SinkExt, StreamExt};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::fs::OpenOptions;
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpStream;
+use tokio::sync::mpsc::{Receiver, UnboundedReceiver, UnboundedSender, channel, unbounded_channel};
+use tokio::time::{sleep, interval, Duration};
+use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream, tungstenite::Message as WsMessage};
+
+use hyperliquid_rust_sdk::{AssetMeta, BaseUrl, Message, Subscription};
+
+use crate::{Candle, MarketCommand, BASE_TF};
+use crate::paper::PaperExecutor;
+use crate::signal::{SignalEngine, EngineCommand, ExecParam, IndexId, SignalEvent};
+use crate::trade_setup::{TradeCommand, TradeInfo, TradeParams, TimeFrame};
+use crate::helper::get_time_now;
+
+
+//A ws frame as it came off the socket, `msg` is the raw {"channel", "data"} object.
+//Warm-up snapshots the market loaded are kept as "warmUp" frames of the same shape
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Record{
+    pub received_at: u64,
+    pub msg: Value,
+}
+
+impl Record{
+
+    pub fn channel(&self) -> Option<&str>{
+        self.msg.get("channel").and_then(Value::as_str)
+    }
+
+    //parses the frame the way the sdk parses a live one
+    pub fn to_message(&self) -> Option<Message>{
+        serde_json::from_value(self.msg.clone()).ok()
+    }
+
+    //the timeframe and candles of a warm-up frame of `asset`
+    pub fn warm_up(&self, asset: &str) -> Option<(TimeFrame, Vec<Candle>)>{
+        if self.channel() != Some(WARM_UP) || self.msg["data"]["s"].as_str() != Some(asset){
+            return None;
+        }
+        let data = &self.msg["data"];
+        let tf = serde_json::from_value(data["tf"].clone()).ok()?;
+        let candles = serde_json::from_value(data["candles"].clone()).ok()?;
+        Some((tf, candles))
+    }
+
+    //the bar of a candle frame of `asset`
+    pub fn candle(&self, asset: &str) -> Option<Candle>{
+        if self.channel() != Some("candle") || self.msg["data"]["s"].as_str() != Some(asset){
+            return None;
+        }
+        let Some(Message::Candle(candle)) = self.to_message() else { return None };
+        let data = &candle.data;
+        let parse = |s: &String| s.parse::<f64>().unwrap_or(0.0);
+        Some(Candle{
+            open_time: data.time_open,
+            open: parse(&data.open),
+            high: parse(&data.high),
+            low: parse(&data.low),
+            close: parse(&data.close),
+            volume: parse(&data.volume),
+            trades: data.num_trades,
+        })
+    }
+}
+
+
+//Flushes a gzip member per batch, the file stays readable up to the last flush if the bot dies
+const FLUSH_EVERY: Duration = Duration::from_secs(1);
+const MAX_BATCH: usize = 512;
+const PING_EVERY: Duration = Duration::from_secs(50);
+const RECONNECT_AFTER: Duration = Duration::from_secs(1);
+const WARM_UP: &str = "warmUp";
+
+type WsStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+#[derive(Debug, Clone)]
+enum SubCommand{
+    Subscribe(Value),
+    Unsubscribe(Value),
+}
+
+//Keeps its own ws connection with the bot's subscriptions and writes every frame verbatim,
+//the sdk hands the bot parsed messages that can't be serialized back
+#[derive(Debug, Clone)]
+pub struct Recorder{
+    subs: UnboundedSender<SubCommand>,
+    records: UnboundedSender<Record>,
+    book: bool,
+}
+
+impl Recorder{
+
+    //`book` also records the l2 book and trades of every market
+    pub fn spawn<P: Into<PathBuf>>(path: P, url: BaseUrl, book: bool) -> Self{
+        let (tx, rx) = unbounded_channel::<Record>();
+        let (subs, subs_rv) = unbounded_channel::<SubCommand>();
+        let path = path.into();
+        tokio::spawn(async move{
+            if let Err(e) = Self::write_loop(path.clone(), rx).await{
+                warn!("Recorder for {} stopped: {}", path.display(), e);
+            }
+        });
+        tokio::spawn(Self::feed_loop(ws_url(url), subs_rv, tx.clone()));
+        Self{ subs, records: tx, book }
+    }
+
+    fn send(&self, sub: Subscription, subscribe: bool){
+        match serde_json::to_value(&sub){
+            Ok(sub) => {
+                let cmd = if subscribe {SubCommand::Subscribe(sub)} else {SubCommand::Unsubscribe(sub)};
+                let _ = self.subs.send(cmd);
+            },
+            Err(e) => warn!("Recorder: bad subscription {:?}, {}", sub, e),
+        }
+    }
+
+    fn market_subs(&self, coin: &str) -> Vec<Subscription>{
+        let mut subs = vec![Subscription::Candle{coin: coin.to_string(), interval: BASE_TF.as_str().to_string()}];
+        if self.book{
+            subs.push(Subscription::L2Book{coin: coin.to_string()});
+            subs.push(Subscription::Trades{coin: coin.to_string()});
+        }
+        subs
+    }
+
+    pub fn watch_market(&self, coin: &str){
+        for sub in self.market_subs(coin){
+            self.send(sub, true);
+        }
+    }
+
+    pub fn unwatch_market(&self, coin: &str){
+        for sub in self.market_subs(coin){
+            self.send(sub, false);
+        }
+    }
+
+    pub fn watch_user(&self, user: H160){
+        self.send(Subscription::UserFills{user}, true);
+    }
+
+    //snapshot a market loaded into its engine, replays load the same history at the same point
+    pub fn record_warm_up(&self, coin: &str, tf: TimeFrame, candles: &[Candle]){
+        let msg = json!({"channel": WARM_UP, "data": {"s": coin, "tf": tf, "candles": candles}});
+        let _ = self.records.send(Record{ received_at: get_time_now(), msg });
+    }
+
+    //connects with every subscription made so far, None if the socket refused one
+    async fn connect(url: &str, subs: &[Value]) -> Option<WsStream>{
+        let mut ws = match connect_async(url).await{
+            Ok((ws, _)) => ws,
+            Err(e) => {
+                warn!("Recorder failed to connect to {}: {}", url, e);
+                return None;
+            },
+        };
+        for sub in subs{
+            ws.send(request("subscribe", sub)).await.ok()?;
+        }
+        Some(ws)
+    }
+
+    //runs until the recorder is dropped, reconnecting whenever the socket goes down
+    async fn feed_loop(url: &'static str, mut subs_rv: UnboundedReceiver<SubCommand>, tx: UnboundedSender<Record>){
+        let mut subs: Vec<Value> = Vec::new();
+        loop{
+            let Some(mut ws) = Self::connect(url, &subs).await else {
+                sleep(RECONNECT_AFTER).await;
+                continue;
+            };
+            let mut ping = interval(PING_EVERY);
+            loop{
+                tokio::select!{
+                    cmd = subs_rv.recv() => {
+                        let Some(cmd) = cmd else { return };
+                        let frame = match cmd{
+                            SubCommand::Subscribe(sub) => {
+                                let frame = request("subscribe", &sub);
+                                subs.push(sub);
+                                frame
+                            },
+                            SubCommand::Unsubscribe(sub) => {
+                                subs.retain(|s| *s != sub);
+                                request("unsubscribe", &sub)
+                            },
+                        };
+                        //a failed send is redone by the resubscription on reconnect
+                        if ws.send(frame).await.is_err(){
+                            break;
+                        }
+                    },
+
+                    frame = ws.next() => {
+                        let text = match frame{
+                            Some(Ok(WsMessage::Text(text))) => text,
+                            Some(Ok(WsMessage::Close(_))) | None => break,
+                            Some(Ok(_)) => continue,
+                            Some(Err(e)) => {
+                                warn!("Recorder feed error: {}", e);
+                                break;
+                            },
+                        };
+                        let received_at = get_time_now();
+                        let Ok(msg) = serde_json::from_str::<Value>(&text) else { continue };
+                        //acks and pongs aren't market data
+                        if matches!(msg.get("channel").and_then(Value::as_str), Some("pong" | "subscriptionResponse")){
+                            continue;
+                        }
+                        if tx.send(Record{ received_at, msg }).is_err(){
+                            return;
+                        }
+                    },
+
+                    _ = ping.tick() => {
+                        if ws.send(WsMessage::Text(json!({"method": "ping"}).to_string())).await.is_err(){
+                            break;
+                        }
+                    },
+                }
+            }
+            warn!("Recorder feed disconnected, reconnecting");
+            sleep(RECONNECT_AFTER).await;
+        }
+    }
+
+    async fn write_loop(path: PathBuf, mut rx: UnboundedReceiver<Record>) -> std::io::Result<()>{
+        if let Some(dir) = path.parent(){
+            tokio::fs::create_dir_all(dir).await?;
+        }
+        let mut file = OpenOptions::new().create(true).append(true).open(&path).await?;
+        info!("Recording market data to {}", path.display());
+
+        let mut batch: Vec<Record> = Vec::with_capacity(MAX_BATCH);
+        let mut ticker = interval(FLUSH_EVERY);
+        loop{
+            tokio::select!{
+                maybe = rx.recv() => match maybe{
+                    Some(record) => {
+                        batch.push(record);
+                        if batch.len() >= MAX_BATCH{
+                            file.write_all(&Self::compress(&batch)?).await?;
+                            batch.clear();
+                        }
+                    },
+                    None => break,
+                },
+                _ = ticker.tick() => {
+                    if !batch.is_empty(){
+                        file.write_all(&Self::compress(&batch)?).await?;
+                        batch.clear();
+                    }
+                },
+            }
+        }
+        if !batch.is_empty(){
+            file.write_all(&Self::compress(&batch)?).await?;
+        }
+        file.flush().await
+    }
+
+    fn compress(batch: &[Record]) -> std::io::Result<Vec<u8>>{
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        for record in batch{
+            serde_json::to_writer(&mut encoder, record)?;
+            encoder.write_all(b"\n")?;
+        }
+        encoder.finish()
+    }
+}
+
+
+fn ws_url(url: BaseUrl) -> &'static str{
+    match url{
+        BaseUrl::Mainnet => "wss://api.hyperliquid.xyz/ws",
+        BaseUrl::Testnet => "wss://api.hyperliquid-testnet.xyz/ws",
+        BaseUrl::Localhost => "ws://localhost:3001/ws",
+    }
+}
+
+fn request(method: &str, sub: &Value) -> WsMessage{
+    WsMessage::Text(json!({"method": method, "subscription": sub}).to_string())
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReplaySpeed{
+    Recorded,
+    Accelerated(f64),
+    Max,
+}
+
+//What a replay decided, in order
+#[derive(Debug, Clone, Default)]
+pub struct ReplayOutcome{
+    pub signals: Vec<SignalEvent>,
+    pub trades: Vec<TradeInfo>,
+    pub margin: f64,
+}
+
+//Feeds a recording back in the order and (optionally) at the pace it was received
+#[derive(Debug, Clone)]
+pub struct Replayer{
+    records: Vec<Record>,
+}
+
+impl Replayer{
+
+    pub fn open<P: AsRef<Path>>(path: P) -> std::io::Result<Self>{
+        let file = std::fs::File::open(path.as_ref())?;
+        let reader = BufReader::new(MultiGzDecoder::new(file));
+
+        let mut records = Vec::new();
+        for line in reader.lines(){
+            let line = match line{
+                Ok(line) => line,
+                Err(e) => {
+                    //truncated last member, everything before it is intact
+                    warn!("Recording {} ends early: {}", path.as_ref().display(), e);
+                    break;
+                },
+            };
+            match serde_json::from_str::<Record>(&line){
+                Ok(record) => records.push(record),
+                Err(e) => warn!("Skipping bad record: {}", e),
+            }
+        }
+        Ok(Self{ records })
+    }
+
+    pub fn records(&self) -> &[Record]{
+        &self.records
+    }
+
+    pub fn candles(&self, asset: &str) -> Vec<Candle>{
+        self.records.iter().filter_map(|r| r.candle(asset)).collect()
+    }
+
+    async fn pace(speed: ReplaySpeed, prev: &mut Option<u64>, at: u64){
+        let wait = prev.map_or(0, |p| at.saturating_sub(p));
+        *prev = Some(at);
+        match speed{
+            ReplaySpeed::Recorded => sleep(Duration::from_millis(wait)).await,
+            ReplaySpeed::Accelerated(x) if x > 0.0 => sleep(Duration::from_secs_f64(wait as f64 / 1000.0 / x)).await,
+            _ => {},
+        }
+    }
+
+    //Runs a SignalEngine on the recorded candles of `asset` with a PaperExecutor filling its trades,
+    //nothing reaches the exchange. Both clocks follow the receive times and each record is settled
+    //before the next one, so a recording replays to the same signals and trades at any speed.
+    //The engine is warmed up from the recorded snapshots, which the market also records when it
+    //reloads a tracker. Recorded user fills aren't played: exits and liquidations come from the paper executor
+    pub async fn play(
+        &self,
+        asset: &AssetMeta,
+        trade_params: TradeParams,
+        config: Option<Vec<IndexId>>,
+        margin: f64,
+        fees: (f64, f64),
+        speed: ReplaySpeed,
+    ) -> ReplayOutcome{
+        //drained after every step, only has to hold what a single step reports
+        let (data_tx, mut data_rv) = channel::<MarketCommand>(64);
+        //the executor is polled after the engine ran, a rendezvous would never have a receiver waiting
+        let (trade_tx, trade_rv) = bounded::<TradeCommand>(1);
+        let (_engine_tx, engine_rv) = unbounded_channel::<EngineCommand>();
+        let (_price_tx, price_rv) = unbounded_channel::<Candle>();
+
+        let lev = trade_params.lev.min(asset.max_leverage);
+        let mut engine = SignalEngine::new(config, trade_params, engine_rv, Some(data_tx.clone()), trade_tx, margin).await;
+        engine.update_exec_params(ExecParam::Lev(lev));
+        let mut paper = PaperExecutor::new(asset.name.clone(), fees, lev, asset.max_leverage, trade_rv, price_rv, data_tx);
+
+        let mut outcome = ReplayOutcome{ margin, ..Default::default() };
+        let mut prev = None;
+        let mut started = false;
+        for record in &self.records{
+            if let Some((tf, candles)) = record.warm_up(&asset.name){
+                engine.set_clock(record.received_at);
+                if started{
+                    engine.reload(tf, candles).await;
+                }else{
+                    engine.load(tf, candles).await;
+                }
+                continue;
+            }
+            let Some(candle) = record.candle(&asset.name) else { continue };
+            started = true;
+            Self::pace(speed, &mut prev, record.received_at).await;
+
+            engine.set_clock(record.received_at);
+            paper.set_clock(record.received_at);
+            //exits and liquidations on the new price first, then the engine's reaction filled at it
+            paper.on_price(candle).await;
+            Self::settle(&mut engine, &mut data_rv, &mut outcome).await;
+            engine.handle(EngineCommand::UpdatePrice(candle)).await;
+            paper.poll_trades().await;
+            Self::settle(&mut engine, &mut data_rv, &mut outcome).await;
+        }
+        paper.flatten().await;
+        Self::settle(&mut engine, &mut data_rv, &mut outcome).await;
+        outcome
+    }
+
+    //routes what the executor and engine reported like Market does
+    async fn settle(engine: &mut SignalEngine, data_rv: &mut Receiver<MarketCommand>, outcome: &mut ReplayOutcome){
+        while let Ok(cmd) = data_rv.try_recv(){
+            match cmd{
+                MarketCommand::ReceiveTrade(trade) => {
+                    outcome.trades.push(trade);
+                    outcome.margin += trade.pnl;
+                    engine.handle(EngineCommand::ReceiveTrade(trade)).await;
+                    engine.update_exec_params(ExecParam::Margin(outcome.margin));
+                },
+                MarketCommand::UpdatePosition(position) => {
+                    engine.handle(EngineCommand::UpdatePosition(position)).await;
+                },
+                MarketCommand::ReceiveSignal(event) => outcome.signals.push(event),
+                //the reload the market fetched follows in the recording as a warm-up frame
+                MarketCommand::ReloadTracker(tf) => info!("Replay: {} tracker skipped bars", tf.as_str()),
+                _ => {},
+            }
+        }
+    }
+}
@@ -0,0 +1,178 @@
+use log::info;
+use serde::{Deserialize, Serialize};
+
+use flume::bounded;
+use hyperliquid_rust_sdk::AssetMeta;
+use tokio::sync::mpsc::{channel, unbounded_channel, UnboundedSender};
+
+use crate::{Candle, IndexId, MarketCommand, MarketUpdate, UpdateFrontend};
+use crate::paper::PaperExecutor;
+use crate::signal::{SignalEngine, EngineCommand, ExecParam, Regime, TimeFrameData};
+use crate::trade_setup::{TradeCommand, TradeParams, TimeFrame};
+
+
+//Alternative engine run on a live market's feed, trading on paper
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ShadowConfig{
+    pub name: String,
+    pub trade_params: TradeParams,
+    pub config: Option<Vec<IndexId>>,
+    //defaults to the live market's margin when attached
+    pub margin: Option<f64>,
+}
+
+impl ShadowConfig{
+    //every timeframe the engine needs warm-up data for
+    pub fn time_frames(&self) -> Vec<TimeFrame>{
+        let mut tfs = vec![self.trade_params.time_frame];
+        tfs.extend(self.config.iter().flatten().map(|id| id.1));
+        tfs.extend(self.trade_params.signal_filter.eval_on_close);
+        tfs.sort_by_key(|tf| tf.to_millis());
+        tfs.dedup();
+        tfs
+    }
+}
+
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ShadowInfo{
+    pub asset: String,
+    pub name: String,
+    pub params: TradeParams,
+    pub start_margin: f64,
+    pub margin: f64,
+    pub pnl: f64,
+    //live market pnl when the shadow was attached, live pnl minus this is comparable to `pnl`
+    pub live_pnl_at_attach: f64,
+    pub trades: usize,
+    pub wins: usize,
+    pub in_position: bool,
+    //false once detached or the market closed
+    pub is_active: bool,
+}
+
+
+//Market side of a shadow, dropping it detaches the shadow
+#[derive(Debug, Clone)]
+pub struct ShadowHandle{
+    pub(crate) name: String,
+    pub(crate) feed: UnboundedSender<Candle>,
+}
+
+
+pub(crate) async fn spawn(
+    asset: &AssetMeta,
+    cfg: ShadowConfig,
+    margin: f64,
+    live_pnl: f64,
+    fees: (f64, f64),
+    price_data: TimeFrameData,
+    bot_tx: UnboundedSender<MarketUpdate>,
+) -> ShadowHandle{
+    let margin = cfg.margin.unwrap_or(margin);
+    let lev = cfg.trade_params.lev.min(asset.max_leverage);
+
+    let (shadow_tx, mut shadow_rv) = channel::<MarketCommand>(7);
+    //a rendezvous like the live executor's, signals are only handed over to an idle executor
+    let (exec_tx, exec_rv) = bounded::<TradeCommand>(0);
+    let (engine_tx, engine_rv) = unbounded_channel::<EngineCommand>();
+    let (paper_tx, paper_rv) = unbounded_channel::<Candle>();
+    let (feed_tx, mut feed_rv) = unbounded_channel::<Candle>();
+
+    let mut engine = SignalEngine::new(cfg.config.clone(), cfg.trade_params.clone(), engine_rv, Some(shadow_tx.clone()), exec_tx, margin).await;
+    for (tf, candles) in price_data{
+        engine.load(tf, candles).await;
+    }
+    let _ = engine_tx.send(EngineCommand::UpdateExecParams(ExecParam::Lev(lev)));
+    let paper = PaperExecutor::new(asset.name.clone(), fees, lev, asset.max_leverage, exec_rv, paper_rv, shadow_tx);
+
+    tokio::spawn(async move {
+        engine.start().await;
+    });
+    tokio::spawn(async move {
+        paper.start().await;
+    });
+
+    let mut info = ShadowInfo{
+        asset: asset.name.clone(),
+        name: cfg.name.clone(),
+        params: cfg.trade_params,
+        start_margin: margin,
+        margin,
+        pnl: 0.0,
+        live_pnl_at_attach: live_pnl,
+        trades: 0,
+        wins: 0,
+        in_position: false,
+        is_active: true,
+    };
+    let publish = move |info: &ShadowInfo| {
+        let _ = bot_tx.send(MarketUpdate::RelayToFrontend(UpdateFrontend::ShadowUpdate(info.clone())));
+    };
+
+    tokio::spawn(async move {
+        info!("Shadow {} attached to {}", info.name, info.asset);
+        publish(&info);
+        let mut regime: Option<Regime> = None;
+        let mut entry_regime: Option<Regime> = None;
+        let mut feed_open = true;
+
+        //runs until the engine and paper executor have both dropped their senders
+        loop{
+            tokio::select!{
+                candle = feed_rv.recv(), if feed_open => {
+                    match candle{
+                        Some(candle) => {
+                            let _ = engine_tx.send(EngineCommand::UpdatePrice(candle));
+                            let _ = paper_tx.send(candle);
+                        },
+                        //detached or market closed: the stopped engine drops its trade sender,
+                        //which makes the paper executor flatten at the last price
+                        None => {
+                            feed_open = false;
+                            let _ = engine_tx.send(EngineCommand::Stop);
+                        },
+                    }
+                },
+
+                cmd = shadow_rv.recv() => {
+                    let Some(cmd) = cmd else { break };
+                    match cmd{
+                        MarketCommand::ReceiveTrade(mut trade_info) => {
+                            trade_info.regime = entry_regime.take().or(regime);
+                            info.pnl += trade_info.pnl;
+                            info.margin += trade_info.pnl;
+                            info.trades += 1;
+                            if trade_info.pnl > 0.0{
+                                info.wins += 1;
+                            }
+                            let _ = engine_tx.send(EngineCommand::ReceiveTrade(trade_info));
+                            let _ = engine_tx.send(EngineCommand::UpdateExecParams(ExecParam::Margin(info.margin)));
+                            publish(&info);
+                        },
+                        MarketCommand::UpdatePosition(position) => {
+                            if position.is_some(){
+                                entry_regime = regime;
+                            }
+                            info.in_position = position.is_some();
+                            let _ = engine_tx.send(EngineCommand::UpdatePosition(position));
+                            publish(&info);
+                        },
+                        MarketCommand::UpdateRegime(r) => regime = Some(r),
+                        //candles are already stored by the live engine, indicators and signals stay internal
+                        _ => {},
+                    }
+                },
+            }
+        }
+
+        info.is_active = false;
+        info.in_position = false;
+        publish(&info);
+        info!("Shadow {} on {} stopped, PNL: {}", info.name, info.asset, info.pnl);
+    });
+
+    ShadowHandle{name: cfg.name, feed: feed_tx}
+}
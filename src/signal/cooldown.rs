@@ -0,0 +1,98 @@
+use serde::{Deserialize, Serialize};
+
+use crate::trade_setup::{TimeFrame, TradeCommand, TradeInfo};
+use crate::helper::get_time_now;
+
+
+//All windows are in seconds, 0 disables the window
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SignalFilter{
+    pub exit_cooldown: u64,
+    pub loss_cooldown: u64,
+    pub same_side_cooldown: u64,
+    pub eval_on_close: Option<TimeFrame>,
+}
+
+
+//Gates entry signals coming out of the strategy, exits always go through
+#[derive(Debug, Clone)]
+pub struct Cooldown{
+    filter: SignalFilter,
+    last_exit: Option<u64>,
+    last_loss: Option<u64>,
+    last_signal: Option<(bool, u64)>, //(is_long, time)
+    clock: Option<u64>, //simulated time in backtests and replays, wall clock when None
+}
+
+
+impl Cooldown{
+
+    pub fn new(filter: SignalFilter) -> Self{
+        Self{
+            filter,
+            last_exit: None,
+            last_loss: None,
+            last_signal: None,
+            clock: None,
+        }
+    }
+
+    pub fn get_filter(&self) -> &SignalFilter{
+        &self.filter
+    }
+
+    pub fn update_filter(&mut self, filter: SignalFilter){
+        self.filter = filter;
+    }
+
+    pub fn set_clock(&mut self, time: u64){
+        self.clock = Some(time);
+    }
+
+    pub fn now(&self) -> u64{
+        self.clock.unwrap_or_else(get_time_now)
+    }
+
+    pub fn on_trade_closed(&mut self, trade: &TradeInfo){
+        let now = self.now();
+        self.last_exit = Some(now);
+        if trade.pnl < 0.0{
+            self.last_loss = Some(now);
+        }
+    }
+
+    pub fn on_signal_sent(&mut self, cmd: &TradeCommand){
+        if let Some(is_long) = cmd.entry_side(){
+            self.last_signal = Some((is_long, self.now()));
+        }
+    }
+
+    pub fn allows(&self, cmd: &TradeCommand) -> bool{
+        let is_long = match cmd.entry_side(){
+            Some(side) => side,
+            None => return true,
+        };
+        let now = self.now();
+
+        if Self::within(now, self.last_exit, self.filter.exit_cooldown){
+            return false;
+        }
+        if Self::within(now, self.last_loss, self.filter.loss_cooldown){
+            return false;
+        }
+        if let Some((last_side, time)) = self.last_signal{
+            if last_side == is_long && Self::within(now, Some(time), self.filter.same_side_cooldown){
+                return false;
+            }
+        }
+        true
+    }
+
+    fn within(now: u64, since: Option<u64>, window_secs: u64) -> bool{
+        match since{
+            Some(time) if window_secs > 0 => now.saturating_sub(time) < window_secs * 1000,
+            _ => false,
+        }
+    }
+}
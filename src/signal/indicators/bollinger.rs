@@ -0,0 +1,58 @@
+use crate::Candle;
+
+use super::{Indicator, Value};
+use super::common::{Window, mean_std};
+
+
+#[derive(Debug, Clone)]
+pub struct BollingerBands{
+    closes: Window,
+    std_dev: f64,
+    last: Option<Value>,
+}
+
+impl BollingerBands{
+    pub fn new(periods: u32, std_dev: f64) -> Self{
+        Self{
+            closes: Window::new(periods),
+            std_dev,
+            last: None,
+        }
+    }
+
+    fn compute(&self, close: f64) -> Option<Value>{
+        if !self.closes.peek_full(){
+            return None;
+        }
+        let (mid, std) = mean_std(self.closes.peek(close));
+        Some(Value::BollingerValue{
+            upper: mid + self.std_dev * std,
+            mid,
+            lower: mid - self.std_dev * std,
+        })
+    }
+}
+
+impl Indicator for BollingerBands{
+    fn update_before_close(&mut self, price: Candle){
+        if let Some(value) = self.compute(price.close){
+            self.last = Some(value);
+        }
+    }
+
+    fn update_after_close(&mut self, price: Candle){
+        if let Some(value) = self.compute(price.close){
+            self.last = Some(value);
+        }
+        self.closes.push(price.close);
+    }
+
+    fn get_last(&self) -> Option<Value>{
+        self.last
+    }
+
+    fn reset(&mut self){
+        self.closes.clear();
+        self.last = None;
+    }
+}
@@ -0,0 +1,167 @@
+use std::collections::VecDeque;
+
+use crate::Candle;
+
+
+//Fixed size window over closed bars
+#[derive(Debug, Clone)]
+pub struct Window{
+    values: VecDeque<f64>,
+    size: usize,
+}
+
+impl Window{
+    pub fn new(size: u32) -> Self{
+        let size = size.max(1) as usize;
+        Self{ values: VecDeque::with_capacity(size), size }
+    }
+
+    pub fn push(&mut self, value: f64){
+        if self.values.len() == self.size{
+            self.values.pop_front();
+        }
+        self.values.push_back(value);
+    }
+
+    pub fn is_full(&self) -> bool{
+        self.values.len() == self.size
+    }
+
+    //the window as it would be after pushing `value`, without committing it
+    pub fn peek(&self, value: f64) -> impl Iterator<Item = f64> + Clone + '_{
+        let skip = if self.is_full() {1} else {0};
+        self.values.iter().copied().skip(skip).chain(std::iter::once(value))
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = f64> + Clone + '_{
+        self.values.iter().copied()
+    }
+
+    pub fn len(&self) -> usize{
+        self.values.len()
+    }
+
+    pub fn peek_full(&self) -> bool{
+        self.values.len() + 1 >= self.size
+    }
+
+    pub fn clear(&mut self){
+        self.values.clear();
+    }
+}
+
+
+pub fn mean_std<I: Iterator<Item = f64> + Clone>(values: I) -> (f64, f64){
+    let n = values.clone().count() as f64;
+    let mean = values.clone().sum::<f64>() / n;
+    let var = values.map(|v| (v - mean).powi(2)).sum::<f64>() / n;
+    (mean, var.sqrt())
+}
+
+
+//EMA seeded with the SMA of the first `periods` values
+#[derive(Debug, Clone)]
+pub struct EmaState{
+    periods: u32,
+    alpha: f64,
+    seed: Vec<f64>,
+    value: Option<f64>,
+}
+
+impl EmaState{
+    pub fn new(periods: u32) -> Self{
+        let periods = periods.max(1);
+        Self{
+            periods,
+            alpha: 2.0 / (periods as f64 + 1.0),
+            seed: Vec::with_capacity(periods as usize),
+            value: None,
+        }
+    }
+
+    pub fn peek(&self, x: f64) -> Option<f64>{
+        match self.value{
+            Some(prev) => Some(prev + self.alpha * (x - prev)),
+            None if self.seed.len() + 1 == self.periods as usize => {
+                Some((self.seed.iter().sum::<f64>() + x) / self.periods as f64)
+            },
+            None => None,
+        }
+    }
+
+    pub fn update(&mut self, x: f64) -> Option<f64>{
+        let next = self.peek(x);
+        if next.is_none(){
+            self.seed.push(x);
+        }else{
+            self.seed.clear();
+        }
+        self.value = next;
+        next
+    }
+
+    pub fn get(&self) -> Option<f64>{
+        self.value
+    }
+
+    pub fn reset(&mut self){
+        self.seed.clear();
+        self.value = None;
+    }
+}
+
+
+pub fn true_range(price: &Candle, prev_close: Option<f64>) -> f64{
+    let hl = price.high - price.low;
+    match prev_close{
+        Some(pc) => hl.max((price.high - pc).abs()).max((price.low - pc).abs()),
+        None => hl,
+    }
+}
+
+
+//Wilder smoothed ATR
+#[derive(Debug, Clone)]
+pub struct AtrState{
+    periods: u32,
+    prev_close: Option<f64>,
+    seed: Vec<f64>,
+    value: Option<f64>,
+}
+
+impl AtrState{
+    pub fn new(periods: u32) -> Self{
+        let periods = periods.max(1);
+        Self{ periods, prev_close: None, seed: Vec::with_capacity(periods as usize), value: None }
+    }
+
+    pub fn peek(&self, price: &Candle) -> Option<f64>{
+        let tr = true_range(price, self.prev_close);
+        let n = self.periods as f64;
+        match self.value{
+            Some(prev) => Some((prev * (n - 1.0) + tr) / n),
+            None if self.seed.len() + 1 == self.periods as usize => {
+                Some((self.seed.iter().sum::<f64>() + tr) / n)
+            },
+            None => None,
+        }
+    }
+
+    pub fn update(&mut self, price: &Candle) -> Option<f64>{
+        let next = self.peek(price);
+        if next.is_none(){
+            self.seed.push(true_range(price, self.prev_close));
+        }else{
+            self.seed.clear();
+        }
+        self.prev_close = Some(price.close);
+        self.value = next;
+        next
+    }
+
+    pub fn reset(&mut self){
+        self.prev_close = None;
+        self.seed.clear();
+        self.value = None;
+    }
+}
@@ -0,0 +1,57 @@
+use crate::Candle;
+
+use super::{Indicator, Value};
+use super::common::Window;
+
+
+#[derive(Debug, Clone)]
+pub struct DonchianChannels{
+    highs: Window,
+    lows: Window,
+    last: Option<Value>,
+}
+
+impl DonchianChannels{
+    pub fn new(periods: u32) -> Self{
+        Self{
+            highs: Window::new(periods),
+            lows: Window::new(periods),
+            last: None,
+        }
+    }
+
+    fn compute(&self, price: &Candle) -> Option<Value>{
+        if !self.highs.peek_full(){
+            return None;
+        }
+        let upper = self.highs.peek(price.high).fold(f64::MIN, f64::max);
+        let lower = self.lows.peek(price.low).fold(f64::MAX, f64::min);
+        Some(Value::DonchianValue{upper, mid: (upper + lower) / 2.0, lower})
+    }
+}
+
+impl Indicator for DonchianChannels{
+    fn update_before_close(&mut self, price: Candle){
+        if let Some(value) = self.compute(&price){
+            self.last = Some(value);
+        }
+    }
+
+    fn update_after_close(&mut self, price: Candle){
+        if let Some(value) = self.compute(&price){
+            self.last = Some(value);
+        }
+        self.highs.push(price.high);
+        self.lows.push(price.low);
+    }
+
+    fn get_last(&self) -> Option<Value>{
+        self.last
+    }
+
+    fn reset(&mut self){
+        self.highs.clear();
+        self.lows.clear();
+        self.last = None;
+    }
+}
@@ -0,0 +1,59 @@
+use crate::Candle;
+
+use super::{Indicator, Value};
+use super::common::{EmaState, AtrState};
+
+
+#[derive(Debug, Clone)]
+pub struct KeltnerChannels{
+    ema: EmaState,
+    atr: AtrState,
+    multiplier: f64,
+    last: Option<Value>,
+}
+
+impl KeltnerChannels{
+    pub fn new(periods: u32, atr_periods: u32, multiplier: f64) -> Self{
+        Self{
+            ema: EmaState::new(periods),
+            atr: AtrState::new(atr_periods),
+            multiplier,
+            last: None,
+        }
+    }
+
+    fn value(&self, mid: Option<f64>, atr: Option<f64>) -> Option<Value>{
+        let (mid, atr) = (mid?, atr?);
+        Some(Value::KeltnerValue{
+            upper: mid + self.multiplier * atr,
+            mid,
+            lower: mid - self.multiplier * atr,
+        })
+    }
+}
+
+impl Indicator for KeltnerChannels{
+    fn update_before_close(&mut self, price: Candle){
+        if let Some(value) = self.value(self.ema.peek(price.close), self.atr.peek(&price)){
+            self.last = Some(value);
+        }
+    }
+
+    fn update_after_close(&mut self, price: Candle){
+        let mid = self.ema.update(price.close);
+        let atr = self.atr.update(&price);
+        if let Some(value) = self.value(mid, atr){
+            self.last = Some(value);
+        }
+    }
+
+    fn get_last(&self) -> Option<Value>{
+        self.last
+    }
+
+    fn reset(&mut self){
+        self.ema.reset();
+        self.atr.reset();
+        self.last = None;
+    }
+}
@@ -0,0 +1,58 @@
+use crate::Candle;
+
+use super::{Indicator, Value};
+use super::common::EmaState;
+
+
+#[derive(Debug, Clone)]
+pub struct Macd{
+    fast: EmaState,
+    slow: EmaState,
+    signal: EmaState,
+    last: Option<Value>,
+}
+
+impl Macd{
+    pub fn new(fast: u32, slow: u32, signal: u32) -> Self{
+        Self{
+            fast: EmaState::new(fast),
+            slow: EmaState::new(slow),
+            signal: EmaState::new(signal),
+            last: None,
+        }
+    }
+
+    fn value(macd: f64, signal: Option<f64>) -> Option<Value>{
+        let signal = signal?;
+        Some(Value::MacdValue{macd, signal, histogram: macd - signal})
+    }
+}
+
+impl Indicator for Macd{
+    fn update_before_close(&mut self, price: Candle){
+        if let (Some(fast), Some(slow)) = (self.fast.peek(price.close), self.slow.peek(price.close)){
+            let macd = fast - slow;
+            self.last = Self::value(macd, self.signal.peek(macd));
+        }
+    }
+
+    fn update_after_close(&mut self, price: Candle){
+        let fast = self.fast.update(price.close);
+        let slow = self.slow.update(price.close);
+        if let (Some(fast), Some(slow)) = (fast, slow){
+            let macd = fast - slow;
+            self.last = Self::value(macd, self.signal.update(macd));
+        }
+    }
+
+    fn get_last(&self) -> Option<Value>{
+        self.last
+    }
+
+    fn reset(&mut self){
+        self.fast.reset();
+        self.slow.reset();
+        self.signal.reset();
+        self.last = None;
+    }
+}
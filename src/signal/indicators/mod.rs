@@ -0,0 +1,180 @@
+mod common;
+mod macd;
+mod bollinger;
+mod keltner;
+mod donchian;
+mod volume;
+mod trend;
+mod pipeline;
+
+use std::fmt::Debug;
+use std::hash::{Hash, Hasher};
+
+use kwant::indicators::{Price, Indicator as KwantIndicator, Value as KwantValue};
+
+use crate::Candle;
+use serde::{Deserialize, Serialize};
+
+pub use macd::Macd;
+pub use bollinger::BollingerBands;
+pub use keltner::KeltnerChannels;
+pub use donchian::DonchianChannels;
+pub use volume::{Vwap, Obv, VolumeMa};
+pub use trend::{Supertrend, Psar, Ichimoku};
+pub use pipeline::{Pipeline, Source, Transform};
+
+
+//Same contract as kwant's Indicator, so the indicators below and kwant's sit behind one Handler
+pub trait Indicator: Debug{
+    fn update_before_close(&mut self, price: Candle);
+    fn update_after_close(&mut self, price: Candle);
+    fn get_last(&self) -> Option<Value>;
+    fn reset(&mut self);
+
+    fn load(&mut self, price_data: &[Candle]){
+        for price in price_data{
+            self.update_after_close(*price);
+        }
+    }
+}
+
+
+#[derive(Debug)]
+pub struct Kwant(pub Box<dyn KwantIndicator>);
+
+impl Indicator for Kwant{
+    fn update_before_close(&mut self, price: Candle){
+        self.0.update_before_close(price.price());
+    }
+    fn update_after_close(&mut self, price: Candle){
+        self.0.update_after_close(price.price());
+    }
+    fn get_last(&self) -> Option<Value>{
+        self.0.get_last().map(Value::from)
+    }
+    fn reset(&mut self){
+        self.0.reset();
+    }
+    fn load(&mut self, price_data: &[Candle]){
+        let prices: Vec<Price> = price_data.iter().map(Candle::price).collect();
+        self.0.load(&prices);
+    }
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Value{
+    RsiValue(f64),
+    StochRsiValue{k: f64, d: f64},
+    EmaValue(f64),
+    EmaCrossValue{short: f64, long: f64, trend: bool},
+    SmaValue(f64),
+    SmaRsiValue(f64),
+    AdxValue(f64),
+    AtrValue(f64),
+    MacdValue{macd: f64, signal: f64, histogram: f64},
+    BollingerValue{upper: f64, mid: f64, lower: f64},
+    KeltnerValue{upper: f64, mid: f64, lower: f64},
+    DonchianValue{upper: f64, mid: f64, lower: f64},
+    VwapValue(f64),
+    AnchoredVwapValue(f64),
+    ObvValue(f64),
+    VolumeMaValue{volume: f64, average: f64},
+    SupertrendValue{level: f64, trend: bool},
+    PsarValue{level: f64, trend: bool},
+    IchimokuValue{tenkan: f64, kijun: f64, span_a: f64, span_b: f64, trend: Option<bool>},
+    PipelineValue{source: Source, transform: Transform, value: f64},
+}
+
+impl Value{
+
+    //direction of the trend following indicators, true => up, None => no opinion
+    pub fn trend(&self) -> Option<bool>{
+        match *self{
+            Value::SupertrendValue{trend, ..} => Some(trend),
+            Value::PsarValue{trend, ..} => Some(trend),
+            Value::IchimokuValue{trend, ..} => trend,
+            _ => None,
+        }
+    }
+
+    //SMA of RSI, reported by SmaOnRsi as a pipeline
+    pub fn smoothed_rsi(&self) -> Option<f64>{
+        match *self{
+            Value::SmaRsiValue(v) => Some(v),
+            Value::PipelineValue{source: Source::Rsi(_), transform: Transform::Sma(_), value} => Some(value),
+            _ => None,
+        }
+    }
+
+    //level a trailing stop can sit at
+    pub fn stop_level(&self) -> Option<f64>{
+        match *self{
+            Value::SupertrendValue{level, ..} => Some(level),
+            Value::PsarValue{level, ..} => Some(level),
+            Value::IchimokuValue{kijun, ..} => Some(kijun),
+            _ => None,
+        }
+    }
+}
+
+impl From<KwantValue> for Value{
+    fn from(value: KwantValue) -> Self{
+        match value{
+            KwantValue::RsiValue(v) => Value::RsiValue(v),
+            KwantValue::StochRsiValue{k, d} => Value::StochRsiValue{k, d},
+            KwantValue::EmaValue(v) => Value::EmaValue(v),
+            KwantValue::EmaCrossValue{short, long, trend} => Value::EmaCrossValue{short, long, trend},
+            KwantValue::SmaValue(v) => Value::SmaValue(v),
+            KwantValue::SmaRsiValue(v) => Value::SmaRsiValue(v),
+            KwantValue::AdxValue(v) => Value::AdxValue(v),
+            KwantValue::AtrValue(v) => Value::AtrValue(v),
+        }
+    }
+}
+
+
+//f64 parameter usable inside IndicatorKind (which has to be Eq + Hash): -0.0 is stored as 0.0 and NaN
+//is rejected, so comparing and hashing the bits agree
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[serde(try_from = "f64", into = "f64")]
+pub struct Factor(f64);
+
+impl Factor{
+    pub fn get(&self) -> f64{
+        self.0
+    }
+}
+
+impl PartialEq for Factor{
+    fn eq(&self, other: &Self) -> bool{
+        self.0.to_bits() == other.0.to_bits()
+    }
+}
+
+impl Eq for Factor {}
+
+impl Hash for Factor{
+    fn hash<H: Hasher>(&self, state: &mut H){
+        self.0.to_bits().hash(state);
+    }
+}
+
+impl TryFrom<f64> for Factor{
+    type Error = String;
+
+    fn try_from(value: f64) -> Result<Self, Self::Error>{
+        if value.is_nan(){
+            return Err("indicator parameter can't be NaN".to_string());
+        }
+        //-0.0 + 0.0 == 0.0
+        Ok(Factor(value + 0.0))
+    }
+}
+
+impl From<Factor> for f64{
+    fn from(value: Factor) -> Self{
+        value.0
+    }
+}
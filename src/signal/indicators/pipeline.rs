@@ -0,0 +1,260 @@
+use serde::{Deserialize, Serialize};
+
+use crate::Candle;
+use crate::signal::IndicatorKind;
+
+use super::{Indicator, Value};
+use super::common::{Window, EmaState, mean_std};
+
+
+//Series a pipeline reads from, either a price field or the main line of an indicator
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Source{
+    Close,
+    Hl2,
+    Volume,
+    Rsi(u32),
+    Atr(u32),
+    Adx(u32),
+    Ema(u32),
+    Sma(u32),
+    Obv,
+    Vwap,
+    MacdHistogram{fast: u32, slow: u32, signal: u32},
+}
+
+impl Source{
+    //indicator backing the source, None for price fields
+    pub fn to_kind(&self) -> Option<IndicatorKind>{
+        match *self{
+            Source::Close | Source::Hl2 | Source::Volume => None,
+            Source::Rsi(periods) => Some(IndicatorKind::Rsi(periods)),
+            Source::Atr(periods) => Some(IndicatorKind::Atr(periods)),
+            Source::Adx(periods) => Some(IndicatorKind::Adx{periods, di_length: periods}),
+            Source::Ema(periods) => Some(IndicatorKind::Ema(periods)),
+            Source::Sma(periods) => Some(IndicatorKind::Sma(periods)),
+            Source::Obv => Some(IndicatorKind::Obv),
+            Source::Vwap => Some(IndicatorKind::Vwap),
+            Source::MacdHistogram{fast, slow, signal} => Some(IndicatorKind::Macd{fast, slow, signal}),
+        }
+    }
+
+    fn read(&self, candle: &Candle, input: Option<Value>) -> Option<f64>{
+        match (*self, input){
+            (Source::Close, _) => Some(candle.close),
+            (Source::Hl2, _) => Some((candle.high + candle.low) / 2.0),
+            (Source::Volume, _) => Some(candle.volume),
+            (_, Some(Value::RsiValue(v))) => Some(v),
+            (_, Some(Value::AtrValue(v))) => Some(v),
+            (_, Some(Value::AdxValue(v))) => Some(v),
+            (_, Some(Value::EmaValue(v))) => Some(v),
+            (_, Some(Value::SmaValue(v))) => Some(v),
+            (_, Some(Value::ObvValue(v))) => Some(v),
+            (_, Some(Value::VwapValue(v))) => Some(v),
+            (_, Some(Value::MacdValue{histogram, ..})) => Some(histogram),
+            _ => None,
+        }
+    }
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Transform{
+    Ema(u32),
+    Sma(u32),
+    Rsi(u32),
+    ZScore(u32),
+}
+
+
+//Wilder RSI over an arbitrary series
+#[derive(Debug, Clone)]
+struct RsiState{
+    periods: u32,
+    prev: Option<f64>,
+    seed: Vec<(f64, f64)>,
+    avg: Option<(f64, f64)>,
+}
+
+impl RsiState{
+    fn new(periods: u32) -> Self{
+        let periods = periods.max(1);
+        Self{ periods, prev: None, seed: Vec::with_capacity(periods as usize), avg: None }
+    }
+
+    fn next_avg(&self, x: f64) -> Option<(f64, f64)>{
+        let prev = self.prev?;
+        let (gain, loss) = ((x - prev).max(0.0), (prev - x).max(0.0));
+        let n = self.periods as f64;
+        match self.avg{
+            Some((g, l)) => Some(((g * (n - 1.0) + gain) / n, (l * (n - 1.0) + loss) / n)),
+            None if self.seed.len() + 1 == self.periods as usize => {
+                let (g, l) = self.seed.iter().fold((gain, loss), |(g, l), (sg, sl)| (g + sg, l + sl));
+                Some((g / n, l / n))
+            },
+            None => None,
+        }
+    }
+
+    fn rsi((gain, loss): (f64, f64)) -> f64{
+        if loss == 0.0{
+            return 100.0;
+        }
+        100.0 - 100.0 / (1.0 + gain / loss)
+    }
+
+    fn peek(&self, x: f64) -> Option<f64>{
+        self.next_avg(x).map(Self::rsi)
+    }
+
+    fn update(&mut self, x: f64) -> Option<f64>{
+        let next = self.next_avg(x);
+        if next.is_none(){
+            if let Some(prev) = self.prev{
+                self.seed.push(((x - prev).max(0.0), (prev - x).max(0.0)));
+            }
+        }else{
+            self.seed.clear();
+        }
+        self.prev = Some(x);
+        self.avg = next;
+        next.map(Self::rsi)
+    }
+
+    fn reset(&mut self){
+        self.prev = None;
+        self.seed.clear();
+        self.avg = None;
+    }
+}
+
+
+#[derive(Debug, Clone)]
+enum TransformState{
+    Ema(EmaState),
+    Sma(Window),
+    Rsi(RsiState),
+    ZScore(Window),
+}
+
+impl TransformState{
+    fn new(transform: Transform) -> Self{
+        match transform{
+            Transform::Ema(periods) => TransformState::Ema(EmaState::new(periods)),
+            Transform::Sma(periods) => TransformState::Sma(Window::new(periods)),
+            Transform::Rsi(periods) => TransformState::Rsi(RsiState::new(periods)),
+            Transform::ZScore(periods) => TransformState::ZScore(Window::new(periods)),
+        }
+    }
+
+    fn peek(&self, x: f64) -> Option<f64>{
+        match self{
+            TransformState::Ema(ema) => ema.peek(x),
+            TransformState::Rsi(rsi) => rsi.peek(x),
+            TransformState::Sma(window) => {
+                if !window.peek_full(){
+                    return None;
+                }
+                Some(mean_std(window.peek(x)).0)
+            },
+            TransformState::ZScore(window) => {
+                if !window.peek_full(){
+                    return None;
+                }
+                let (mean, std) = mean_std(window.peek(x));
+                if std == 0.0 {Some(0.0)} else {Some((x - mean) / std)}
+            },
+        }
+    }
+
+    fn update(&mut self, x: f64) -> Option<f64>{
+        match self{
+            TransformState::Ema(ema) => ema.update(x),
+            TransformState::Rsi(rsi) => rsi.update(x),
+            TransformState::Sma(_) | TransformState::ZScore(_) => {
+                let value = self.peek(x);
+                if let TransformState::Sma(window) | TransformState::ZScore(window) = self{
+                    window.push(x);
+                }
+                value
+            },
+        }
+    }
+
+    fn reset(&mut self){
+        match self{
+            TransformState::Ema(ema) => ema.reset(),
+            TransformState::Rsi(rsi) => rsi.reset(),
+            TransformState::Sma(window) | TransformState::ZScore(window) => window.clear(),
+        }
+    }
+}
+
+
+//`transform` applied to the series produced by `source`, e.g. EMA of ATR or z-score of the close.
+//The value is reported with both so strategies can tell pipelines apart
+#[derive(Debug)]
+pub struct Pipeline{
+    source: Source,
+    input: Option<Box<dyn Indicator>>,
+    kind: Transform,
+    transform: TransformState,
+    last: Option<Value>,
+}
+
+impl Pipeline{
+    pub fn new(source: Source, input: Option<Box<dyn Indicator>>, transform: Transform) -> Self{
+        Self{
+            source,
+            input,
+            kind: transform,
+            transform: TransformState::new(transform),
+            last: None,
+        }
+    }
+
+    fn read(&self, candle: &Candle) -> Option<f64>{
+        let input = self.input.as_ref().and_then(|ind| ind.get_last());
+        self.source.read(candle, input)
+    }
+
+    fn value(&self, value: f64) -> Value{
+        Value::PipelineValue{source: self.source, transform: self.kind, value}
+    }
+}
+
+impl Indicator for Pipeline{
+    fn update_before_close(&mut self, price: Candle){
+        if let Some(input) = &mut self.input{
+            input.update_before_close(price);
+        }
+        if let Some(value) = self.read(&price).and_then(|x| self.transform.peek(x)){
+            self.last = Some(self.value(value));
+        }
+    }
+
+    fn update_after_close(&mut self, price: Candle){
+        if let Some(input) = &mut self.input{
+            input.update_after_close(price);
+        }
+        if let Some(x) = self.read(&price){
+            if let Some(value) = self.transform.update(x){
+                self.last = Some(self.value(value));
+            }
+        }
+    }
+
+    fn get_last(&self) -> Option<Value>{
+        self.last
+    }
+
+    fn reset(&mut self){
+        if let Some(input) = &mut self.input{
+            input.reset();
+        }
+        self.transform.reset();
+        self.last = None;
+    }
+}
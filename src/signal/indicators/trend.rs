@@ -0,0 +1,302 @@
+use std::collections::VecDeque;
+
+use crate::Candle;
+
+use super::{Indicator, Value};
+use super::common::{AtrState, Window};
+
+
+#[derive(Debug, Clone, Copy)]
+struct SupertrendState{
+    upper: f64,
+    lower: f64,
+    trend: bool,
+    close: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct Supertrend{
+    atr: AtrState,
+    multiplier: f64,
+    state: Option<SupertrendState>,
+    last: Option<Value>,
+}
+
+impl Supertrend{
+    pub fn new(atr_periods: u32, multiplier: f64) -> Self{
+        Self{
+            atr: AtrState::new(atr_periods),
+            multiplier,
+            state: None,
+            last: None,
+        }
+    }
+
+    fn step(&self, candle: &Candle, atr: f64) -> SupertrendState{
+        let hl2 = (candle.high + candle.low) / 2.0;
+        let basic_upper = hl2 + self.multiplier * atr;
+        let basic_lower = hl2 - self.multiplier * atr;
+
+        let prev = match self.state{
+            Some(prev) => prev,
+            None => return SupertrendState{upper: basic_upper, lower: basic_lower, trend: candle.close >= hl2, close: candle.close},
+        };
+
+        let upper = if basic_upper < prev.upper || prev.close > prev.upper {basic_upper} else {prev.upper};
+        let lower = if basic_lower > prev.lower || prev.close < prev.lower {basic_lower} else {prev.lower};
+        let trend = if prev.trend {candle.close >= lower} else {candle.close > upper};
+
+        SupertrendState{upper, lower, trend, close: candle.close}
+    }
+
+    fn value(state: &SupertrendState) -> Value{
+        Value::SupertrendValue{
+            level: if state.trend {state.lower} else {state.upper},
+            trend: state.trend,
+        }
+    }
+}
+
+impl Indicator for Supertrend{
+    fn update_before_close(&mut self, price: Candle){
+        if let Some(atr) = self.atr.peek(&price){
+            self.last = Some(Self::value(&self.step(&price, atr)));
+        }
+    }
+
+    fn update_after_close(&mut self, price: Candle){
+        if let Some(atr) = self.atr.update(&price){
+            let state = self.step(&price, atr);
+            self.last = Some(Self::value(&state));
+            self.state = Some(state);
+        }
+    }
+
+    fn get_last(&self) -> Option<Value>{
+        self.last
+    }
+
+    fn reset(&mut self){
+        self.atr.reset();
+        self.state = None;
+        self.last = None;
+    }
+}
+
+
+#[derive(Debug, Clone, Copy)]
+struct PsarState{
+    sar: f64,
+    ep: f64,
+    af: f64,
+    trend: bool,
+    high: f64,
+    low: f64,
+    prev_high: f64,
+    prev_low: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct Psar{
+    step: f64,
+    max: f64,
+    first: Option<Candle>,
+    state: Option<PsarState>,
+    last: Option<Value>,
+}
+
+impl Psar{
+    pub fn new(step: f64, max: f64) -> Self{
+        Self{ step, max, first: None, state: None, last: None }
+    }
+
+    fn next(&self, candle: &Candle) -> Option<PsarState>{
+        let prev = match self.state{
+            Some(prev) => prev,
+            None => {
+                //second bar decides the initial direction
+                let first = self.first?;
+                let trend = candle.close >= first.close;
+                return Some(PsarState{
+                    sar: if trend {first.low} else {first.high},
+                    ep: if trend {candle.high} else {candle.low},
+                    af: self.step,
+                    trend,
+                    high: candle.high,
+                    low: candle.low,
+                    prev_high: first.high,
+                    prev_low: first.low,
+                });
+            },
+        };
+
+        let mut sar = prev.sar + prev.af * (prev.ep - prev.sar);
+        let (mut ep, mut af, mut trend) = (prev.ep, prev.af, prev.trend);
+
+        if trend{
+            sar = sar.min(prev.low).min(prev.prev_low);
+            if candle.low < sar{
+                trend = false;
+                sar = ep;
+                ep = candle.low;
+                af = self.step;
+            }else if candle.high > ep{
+                ep = candle.high;
+                af = (af + self.step).min(self.max);
+            }
+        }else{
+            sar = sar.max(prev.high).max(prev.prev_high);
+            if candle.high > sar{
+                trend = true;
+                sar = ep;
+                ep = candle.high;
+                af = self.step;
+            }else if candle.low < ep{
+                ep = candle.low;
+                af = (af + self.step).min(self.max);
+            }
+        }
+
+        Some(PsarState{sar, ep, af, trend, high: candle.high, low: candle.low, prev_high: prev.high, prev_low: prev.low})
+    }
+}
+
+impl Indicator for Psar{
+    fn update_before_close(&mut self, price: Candle){
+        if let Some(state) = self.next(&price){
+            self.last = Some(Value::PsarValue{level: state.sar, trend: state.trend});
+        }
+    }
+
+    fn update_after_close(&mut self, price: Candle){
+        match self.next(&price){
+            Some(state) => {
+                self.last = Some(Value::PsarValue{level: state.sar, trend: state.trend});
+                self.state = Some(state);
+            },
+            None => self.first = Some(price),
+        }
+    }
+
+    fn get_last(&self) -> Option<Value>{
+        self.last
+    }
+
+    fn reset(&mut self){
+        self.first = None;
+        self.state = None;
+        self.last = None;
+    }
+}
+
+
+//Midpoint of the highest high and lowest low over a window
+#[derive(Debug, Clone)]
+struct MidRange{
+    highs: Window,
+    lows: Window,
+}
+
+impl MidRange{
+    fn new(periods: u32) -> Self{
+        Self{ highs: Window::new(periods), lows: Window::new(periods) }
+    }
+
+    fn peek(&self, candle: &Candle) -> Option<f64>{
+        if !self.highs.peek_full(){
+            return None;
+        }
+        let high = self.highs.peek(candle.high).fold(f64::MIN, f64::max);
+        let low = self.lows.peek(candle.low).fold(f64::MAX, f64::min);
+        Some((high + low) / 2.0)
+    }
+
+    fn push(&mut self, candle: &Candle){
+        self.highs.push(candle.high);
+        self.lows.push(candle.low);
+    }
+
+    fn clear(&mut self){
+        self.highs.clear();
+        self.lows.clear();
+    }
+}
+
+//Spans are displaced forward by `kijun` bars, the cloud at the current bar is the one computed kijun bars ago
+#[derive(Debug, Clone)]
+pub struct Ichimoku{
+    tenkan: MidRange,
+    kijun: MidRange,
+    senkou: MidRange,
+    displacement: usize,
+    spans: VecDeque<(f64, f64)>,
+    last: Option<Value>,
+}
+
+impl Ichimoku{
+    pub fn new(tenkan: u32, kijun: u32, senkou: u32) -> Self{
+        Self{
+            tenkan: MidRange::new(tenkan),
+            kijun: MidRange::new(kijun),
+            senkou: MidRange::new(senkou),
+            displacement: kijun.max(1) as usize,
+            spans: VecDeque::with_capacity(kijun.max(1) as usize),
+            last: None,
+        }
+    }
+
+    fn compute(&self, candle: &Candle) -> Option<(Value, (f64, f64))>{
+        let tenkan = self.tenkan.peek(candle)?;
+        let kijun = self.kijun.peek(candle)?;
+        let span_b = self.senkou.peek(candle)?;
+        let span_a = (tenkan + kijun) / 2.0;
+
+        let (cloud_a, cloud_b) = if self.spans.len() == self.displacement{
+            self.spans[0]
+        }else{
+            (span_a, span_b)
+        };
+        let trend = if candle.close > cloud_a.max(cloud_b){
+            Some(true)
+        }else if candle.close < cloud_a.min(cloud_b){
+            Some(false)
+        }else{
+            None
+        };
+
+        Some((Value::IchimokuValue{tenkan, kijun, span_a: cloud_a, span_b: cloud_b, trend}, (span_a, span_b)))
+    }
+}
+
+impl Indicator for Ichimoku{
+    fn update_before_close(&mut self, price: Candle){
+        if let Some((value, _)) = self.compute(&price){
+            self.last = Some(value);
+        }
+    }
+
+    fn update_after_close(&mut self, price: Candle){
+        if let Some((value, spans)) = self.compute(&price){
+            self.last = Some(value);
+            if self.spans.len() == self.displacement{
+                self.spans.pop_front();
+            }
+            self.spans.push_back(spans);
+        }
+        self.tenkan.push(&price);
+        self.kijun.push(&price);
+        self.senkou.push(&price);
+    }
+
+    fn get_last(&self) -> Option<Value>{
+        self.last
+    }
+
+    fn reset(&mut self){
+        self.tenkan.clear();
+        self.kijun.clear();
+        self.senkou.clear();
+        self.spans.clear();
+        self.last = None;
+    }
+}
@@ -0,0 +1,183 @@
+use crate::Candle;
+
+use super::{Indicator, Value};
+use super::common::{Window, EmaState};
+
+
+const DAY_MS: u64 = 24 * 60 * 60 * 1000;
+
+
+//VWAP accumulated from `anchor` (ms) or, with no anchor, reset every UTC day
+#[derive(Debug, Clone)]
+pub struct Vwap{
+    anchor: Option<u64>,
+    session: Option<u64>,
+    cum_pv: f64,
+    cum_vol: f64,
+    last: Option<Value>,
+}
+
+impl Vwap{
+    pub fn session() -> Self{
+        Self{ anchor: None, session: None, cum_pv: 0.0, cum_vol: 0.0, last: None }
+    }
+
+    pub fn anchored(anchor: u64) -> Self{
+        Self{ anchor: Some(anchor), ..Self::session() }
+    }
+
+    //running sums the candle adds to, None if the candle is before the anchor
+    fn base(&self, candle: &Candle) -> Option<(f64, f64)>{
+        match self.anchor{
+            Some(anchor) if candle.open_time < anchor => None,
+            Some(_) => Some((self.cum_pv, self.cum_vol)),
+            None if self.session != Some(candle.open_time / DAY_MS) => Some((0.0, 0.0)),
+            None => Some((self.cum_pv, self.cum_vol)),
+        }
+    }
+
+    fn compute(&self, candle: &Candle) -> Option<(f64, f64, Value)>{
+        let (pv, vol) = self.base(candle)?;
+        let pv = pv + candle.typical_price() * candle.volume;
+        let vol = vol + candle.volume;
+        if vol <= 0.0{
+            return None;
+        }
+        let value = match self.anchor{
+            Some(_) => Value::AnchoredVwapValue(pv / vol),
+            None => Value::VwapValue(pv / vol),
+        };
+        Some((pv, vol, value))
+    }
+}
+
+impl Indicator for Vwap{
+    fn update_before_close(&mut self, price: Candle){
+        if let Some((_, _, value)) = self.compute(&price){
+            self.last = Some(value);
+        }
+    }
+
+    fn update_after_close(&mut self, price: Candle){
+        if let Some((pv, vol, value)) = self.compute(&price){
+            self.cum_pv = pv;
+            self.cum_vol = vol;
+            self.session = Some(price.open_time / DAY_MS);
+            self.last = Some(value);
+        }
+    }
+
+    fn get_last(&self) -> Option<Value>{
+        self.last
+    }
+
+    fn reset(&mut self){
+        self.session = None;
+        self.cum_pv = 0.0;
+        self.cum_vol = 0.0;
+        self.last = None;
+    }
+}
+
+
+#[derive(Debug, Clone, Default)]
+pub struct Obv{
+    obv: f64,
+    prev_close: Option<f64>,
+    last: Option<Value>,
+}
+
+impl Obv{
+    pub fn new() -> Self{
+        Self::default()
+    }
+
+    fn compute(&self, candle: &Candle) -> f64{
+        match self.prev_close{
+            Some(prev) if candle.close > prev => self.obv + candle.volume,
+            Some(prev) if candle.close < prev => self.obv - candle.volume,
+            _ => self.obv,
+        }
+    }
+}
+
+impl Indicator for Obv{
+    fn update_before_close(&mut self, price: Candle){
+        if self.prev_close.is_some(){
+            self.last = Some(Value::ObvValue(self.compute(&price)));
+        }
+    }
+
+    fn update_after_close(&mut self, price: Candle){
+        self.obv = self.compute(&price);
+        self.prev_close = Some(price.close);
+        self.last = Some(Value::ObvValue(self.obv));
+    }
+
+    fn get_last(&self) -> Option<Value>{
+        self.last
+    }
+
+    fn reset(&mut self){
+        *self = Self::default();
+    }
+}
+
+
+#[derive(Debug, Clone)]
+enum Average{
+    Sma(Window),
+    Ema(EmaState),
+}
+
+//Moving average of volume, reported with the last closed bar's volume so strategies can compare the two
+#[derive(Debug, Clone)]
+pub struct VolumeMa{
+    average: Average,
+    last: Option<Value>,
+}
+
+impl VolumeMa{
+    pub fn sma(periods: u32) -> Self{
+        Self{ average: Average::Sma(Window::new(periods)), last: None }
+    }
+
+    pub fn ema(periods: u32) -> Self{
+        Self{ average: Average::Ema(EmaState::new(periods)), last: None }
+    }
+}
+
+impl Indicator for VolumeMa{
+    //the volume of a forming bar isn't comparable to the average of full bars,
+    //the value stays at the last closed bar until the next close
+    fn update_before_close(&mut self, _price: Candle){}
+
+    fn update_after_close(&mut self, price: Candle){
+        let average = match &mut self.average{
+            Average::Sma(window) => {
+                window.push(price.volume);
+                if window.is_full(){
+                    Some(window.values().sum::<f64>() / window.len() as f64)
+                }else{
+                    None
+                }
+            },
+            Average::Ema(ema) => ema.update(price.volume),
+        };
+        if let Some(average) = average{
+            self.last = Some(Value::VolumeMaValue{volume: price.volume, average});
+        }
+    }
+
+    fn get_last(&self) -> Option<Value>{
+        self.last
+    }
+
+    fn reset(&mut self){
+        match &mut self.average{
+            Average::Sma(window) => window.clear(),
+            Average::Ema(ema) => ema.reset(),
+        }
+        self.last = None;
+    }
+}
@@ -1,5 +1,8 @@
 mod signal;
 mod types;
+mod regime;
+mod cooldown;
+pub mod indicators;
 
 pub use signal::{
     SignalEngine,
@@ -16,5 +19,26 @@ pub use types::{
     TimeFrameData,
     EditType,
     Entry,
+    SkipReason,
+    SignalStatus,
+    SignalEvent,
+    MarketSignalEvent,
 };
 
+pub use indicators::{
+    Value,
+    Factor,
+    Source,
+    Transform,
+};
+
+pub use regime::{
+    Regime,
+    RegimeConfig,
+    RegimeDetector,
+};
+
+pub use cooldown::{
+    Cooldown,
+    SignalFilter,
+};
@@ -0,0 +1,189 @@
+use log::info;
+use serde::{Deserialize, Serialize};
+
+use crate::trade_setup::TimeFrame;
+use crate::Candle;
+use super::types::{Tracker, IndicatorKind};
+use super::indicators::Value;
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Regime{
+    Trending,
+    Ranging,
+    Volatile,
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RegimeConfig{
+    pub adx_periods: u32,
+    pub atr_periods: u32,
+    pub ema_periods: u32,
+    pub adx_trend: f64,        //ADX above this is a trend candidate
+    pub min_slope: f64,        //min EMA slope in % per bar to call a trend
+    pub volatility_ratio: f64, //ATR / avg ATR above this is volatile
+    pub hysteresis: u32,       //consecutive bars needed to switch regime
+}
+
+impl Default for RegimeConfig{
+    fn default() -> Self{
+        Self{
+            adx_periods: 14,
+            atr_periods: 14,
+            ema_periods: 50,
+            adx_trend: 25.0,
+            min_slope: 0.05,
+            volatility_ratio: 1.5,
+            hysteresis: 3,
+        }
+    }
+}
+
+
+//Classifies the market on every closed bar from ADX, ATR and EMA slope.
+//A new regime has to be observed `hysteresis` bars in a row before it replaces the current one.
+#[derive(Debug)]
+pub struct RegimeDetector{
+    tracker: Tracker,
+    tf: TimeFrame,
+    config: RegimeConfig,
+    current: Option<Regime>,
+    candidate: Option<Regime>,
+    streak: u32,
+    prev_ema: Option<f64>,
+    atr_avg: Option<f64>,
+}
+
+
+impl RegimeDetector{
+
+    const ATR_AVG_ALPHA: f64 = 0.05;
+
+    pub fn new(tf: TimeFrame, config: RegimeConfig) -> Self{
+        let mut tracker = Tracker::new(tf);
+        tracker.add_indicator(Self::adx_kind(&config), false);
+        tracker.add_indicator(IndicatorKind::Atr(config.atr_periods), false);
+        tracker.add_indicator(IndicatorKind::Ema(config.ema_periods), false);
+
+        Self{
+            tracker,
+            tf,
+            config,
+            current: None,
+            candidate: None,
+            streak: 0,
+            prev_ema: None,
+            atr_avg: None,
+        }
+    }
+
+    fn adx_kind(config: &RegimeConfig) -> IndicatorKind{
+        IndicatorKind::Adx{periods: config.adx_periods, di_length: config.adx_periods}
+    }
+
+    pub fn get_tf(&self) -> TimeFrame{
+        self.tf
+    }
+
+    pub fn get_regime(&self) -> Option<Regime>{
+        self.current
+    }
+
+    pub async fn load<I: IntoIterator<Item=Candle>>(&mut self, price_data: I, now: u64){
+        self.tracker.load(price_data, now).await;
+        let (_adx, atr, ema) = self.read_values();
+        self.prev_ema = ema;
+        self.atr_avg = atr;
+    }
+
+    //refills the series after a gap, the detected regime is kept
+    pub async fn reload<I: IntoIterator<Item=Candle>>(&mut self, price_data: I, now: u64){
+        self.tracker.reset();
+        self.load(price_data, now).await;
+    }
+
+    pub fn reset(&mut self){
+        self.tracker.reset();
+        self.current = None;
+        self.candidate = None;
+        self.streak = 0;
+        self.prev_ema = None;
+        self.atr_avg = None;
+    }
+
+    fn read_values(&self) -> (Option<f64>, Option<f64>, Option<f64>){
+        let adx = match self.tracker.get_value(&Self::adx_kind(&self.config)){
+            Some(Value::AdxValue(v)) => Some(v),
+            _ => None,
+        };
+        let atr = match self.tracker.get_value(&IndicatorKind::Atr(self.config.atr_periods)){
+            Some(Value::AtrValue(v)) => Some(v),
+            _ => None,
+        };
+        let ema = match self.tracker.get_value(&IndicatorKind::Ema(self.config.ema_periods)){
+            Some(Value::EmaValue(v)) => Some(v),
+            _ => None,
+        };
+        (adx, atr, ema)
+    }
+
+    //returns the new regime when it changes
+    pub fn update(&mut self, price: Candle) -> Option<Regime>{
+        if !self.tracker.digest(price){
+            return None;
+        }
+
+        let raw = self.classify()?;
+
+        if Some(raw) == self.current{
+            self.candidate = None;
+            self.streak = 0;
+            return None;
+        }
+
+        if Some(raw) == self.candidate{
+            self.streak += 1;
+        }else{
+            self.candidate = Some(raw);
+            self.streak = 1;
+        }
+
+        if self.current.is_none() || self.streak >= self.config.hysteresis{
+            info!("Regime changed: {:?} -> {:?}", self.current, raw);
+            self.current = Some(raw);
+            self.candidate = None;
+            self.streak = 0;
+            return self.current;
+        }
+
+        None
+    }
+
+    fn classify(&mut self) -> Option<Regime>{
+        let (adx, atr, ema) = self.read_values();
+        let (adx, atr, ema) = (adx?, atr?, ema?);
+
+        let slope = match self.prev_ema{
+            Some(prev) if prev != 0.0 => (ema - prev) / prev * 100.0,
+            _ => 0.0,
+        };
+        self.prev_ema = Some(ema);
+
+        let atr_avg = match self.atr_avg{
+            Some(avg) => avg + (atr - avg) * Self::ATR_AVG_ALPHA,
+            None => atr,
+        };
+        self.atr_avg = Some(atr_avg);
+
+        if atr_avg > 0.0 && atr / atr_avg >= self.config.volatility_ratio{
+            Some(Regime::Volatile)
+        }else if adx >= self.config.adx_trend && slope.abs() >= self.config.min_slope{
+            Some(Regime::Trending)
+        }else{
+            Some(Regime::Ranging)
+        }
+    }
+}
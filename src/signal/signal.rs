@@ -1,18 +1,21 @@
 use std::collections::HashMap;
 use std::hash::BuildHasherDefault;
+use std::mem::{discriminant, Discriminant};
 use rustc_hash::FxHasher;
 
 use log::info;
 
-use kwant::indicators::{Price, Indicator, Value};
+use super::indicators::Value;
 
-use crate::trade_setup::{TimeFrame,TradeParams, TradeCommand};
-use crate::strategy::Strategy;
-use crate::{IndicatorData, MarketCommand};
+use crate::trade_setup::{TimeFrame,TradeParams, TradeCommand, TradeInfo, PositionSnapshot};
+use crate::strategy::{Strategy, Vote};
+use crate::{IndicatorData, MarketCommand, Candle};
 
 use tokio::sync::mpsc::{UnboundedReceiver, Sender as tokioSender, unbounded_channel};
 use flume::{Sender, bounded};
 
+use super::regime::{Regime, RegimeConfig, RegimeDetector};
+use super::cooldown::{Cooldown, SignalFilter};
 use super::types::{
     Tracker,
     IndexId,
@@ -21,6 +24,9 @@ use super::types::{
     TimeFrameData,
     EditType,
     Entry,
+    SignalEvent,
+    SignalStatus,
+    SkipReason,
 };
 
 
@@ -31,6 +37,13 @@ pub struct SignalEngine{
     trackers: HashMap<TimeFrame, Box<Tracker>, BuildHasherDefault<FxHasher>>, 
     strategy: Strategy,
     exec_params: ExecParams,
+    position: Option<PositionSnapshot>,
+    regime: RegimeDetector,
+    cooldown: Cooldown,
+    //last published (command, side, outcome) and the open time of the trade tf bar it was published on
+    last_event: Option<(Discriminant<TradeCommand>, Option<bool>, SignalStatus, u64)>,
+    entries_paused: bool,
+    tick: u64,
 }
 
 
@@ -61,6 +74,10 @@ impl SignalEngine{
                 }
             }};
             
+        if let Some(tf) = trade_params.signal_filter.eval_on_close{
+            trackers.entry(tf).or_insert_with(|| Box::new(Tracker::new(tf)));
+        }
+            
         SignalEngine{
             engine_rv,
             trade_tx,
@@ -68,6 +85,12 @@ impl SignalEngine{
             trackers,
             strategy: trade_params.strategy,
             exec_params: ExecParams::new(margin, trade_params.lev, trade_params.time_frame),
+            position: None,
+            regime: RegimeDetector::new(trade_params.time_frame, RegimeConfig::default()),
+            cooldown: Cooldown::new(trade_params.signal_filter),
+            last_event: None,
+            entries_paused: false,
+            tick: 0,
         }
     }
 
@@ -75,6 +98,7 @@ impl SignalEngine{
         for (_tf, tracker) in &mut self.trackers{
             tracker.reset();
         }
+        self.regime.reset();
     } 
     
     
@@ -147,17 +171,188 @@ impl SignalEngine{
         &self.strategy
     }
 
-    pub async fn load<I:IntoIterator<Item=Price>>(&mut self,tf: TimeFrame, price_data: I) {
+    pub fn get_position(&self) -> Option<&PositionSnapshot>{
+        self.position.as_ref()
+    }
+
+    pub fn update_position(&mut self, position: Option<PositionSnapshot>){
+        //a new position or a close makes the next signal a new event, even on the same bar
+        if position.is_some() != self.position.is_some(){
+            self.last_event = None;
+        }
+        self.position = position;
+        info!("Position updated: {:?}", self.position);
+    }
+
+    pub async fn load<I:IntoIterator<Item=Candle>>(&mut self,tf: TimeFrame, price_data: I) {
+        let price_data: Vec<Candle> = price_data.into_iter().collect();
+        let now = self.cooldown.now();
+        if tf == self.regime.get_tf(){
+            self.regime.load(price_data.clone(), now).await;
+        }
         if let Some(tracker) = self.trackers.get_mut(&tf){
-            tracker.load(price_data).await
+            tracker.load(price_data, now).await
+        }
+    }
+
+    //replaces a timeframe's history, used after its tracker skipped bars
+    pub async fn reload(&mut self, tf: TimeFrame, price_data: Vec<Candle>){
+        let now = self.cooldown.now();
+        if tf == self.regime.get_tf(){
+            self.regime.reload(price_data.clone(), now).await;
+        }
+        if let Some(tracker) = self.trackers.get_mut(&tf){
+            tracker.reset();
+            tracker.load(price_data, now).await;
+        }
+        info!("{} tracker reloaded after a gap", tf.as_str());
+    }
+
+    //timeframes whose tracker skipped bars since the last call
+    fn take_gaps(&mut self) -> Vec<TimeFrame>{
+        self.trackers.iter_mut().filter_map(|(tf, tracker)| tracker.take_gap().map(|_| *tf)).collect()
+    }
+
+    pub fn update_signal_filter(&mut self, filter: SignalFilter){
+        if let Some(tf) = filter.eval_on_close{
+            self.trackers.entry(tf).or_insert_with(|| Box::new(Tracker::new(tf)));
+        }
+        self.cooldown.update_filter(filter);
+        info!("Signal filter updated: {:?}", filter);
+    }
+
+    pub fn get_regime(&self) -> Option<Regime>{
+        self.regime.get_regime()
+    }
+
+    async fn on_regime_change(&mut self, regime: Regime){
+        if let Strategy::Custom(strat) = &mut self.strategy{
+            if strat.apply_regime(regime){
+                info!("Regime {:?}: strategy switched to {:?}", regime, strat);
+            }
+        }
+        if let Some(sender) = &self.data_tx{
+            let _ = sender.send(MarketCommand::UpdateRegime(regime)).await;
         }
     }
 
 
     fn get_signal(&self, price: f64, values: Vec<Value>) -> Option<TradeCommand>{
        
-        match self.strategy{
-            Strategy::Custom(brr) => brr.generate_signal(values, price, self.exec_params)
+        match &self.strategy{
+            Strategy::Custom(brr) => brr.generate_signal(values, price, self.exec_params, self.position.as_ref()),
+            Strategy::Ensemble(ens) => ens.generate_signal(&values, price, self.exec_params, self.position.as_ref()),
+        }
+    }
+
+    fn gate(&self, trade: &TradeCommand) -> Option<SkipReason>{
+        if !self.cooldown.allows(trade){
+            return Some(SkipReason::Cooldown);
+        }
+        if self.entries_paused && trade.entry_side().is_some(){
+            return Some(SkipReason::FeedStale);
+        }
+        if self.position.is_some() && trade.entry_side().is_some(){
+            return Some(SkipReason::PositionOpen);
+        }
+        None
+    }
+
+    fn dispatch(&mut self, trade: TradeCommand) -> SignalStatus{
+        if let Some(reason) = self.gate(&trade){
+            return SignalStatus::Skipped(reason);
+        }
+        match self.trade_tx.try_send(trade){
+            Ok(_) => {
+                self.cooldown.on_signal_sent(&trade);
+                SignalStatus::Accepted
+            },
+            Err(_) => SignalStatus::Skipped(SkipReason::ExecutorBusy),
+        }
+    }
+
+    //only publishes when the signal, its outcome or the trade tf bar changes,
+    //a condition holding over many ticks of one bar is one event
+    async fn publish_signal(&mut self, price: f64, open_time: u64, command: TradeCommand, status: SignalStatus){
+        let key = (discriminant(&command), command.entry_side(), status, self.exec_params.tf.bar_open(open_time));
+        if self.last_event == Some(key){
+            return;
+        }
+        self.last_event = Some(key);
+
+        let event = SignalEvent::new(self.cooldown.now(), self.strategy.name(), self.get_indicators_data(), price, command, status);
+        info!("Signal: {:?} {:?} @ {}", event.command, event.status, event.price);
+        if let Some(sender) = &self.data_tx{
+            let _ = sender.send(MarketCommand::ReceiveSignal(event)).await;
+        }
+    }
+
+    //feeds the trackers, returns whether the eval timeframe closed and the bars that closed
+    fn digest(&mut self, price: Candle) -> (bool, Vec<(TimeFrame, Candle)>){
+        let eval_tf = self.cooldown.get_filter().eval_on_close;
+        let mut eval_closed = false;
+        let mut closed_bars = Vec::new();
+        for (tf, tracker) in &mut self.trackers{
+            if tracker.digest(price){
+                if Some(*tf) == eval_tf{
+                    eval_closed = true;
+                }
+                if let Some(bar) = tracker.price_data.back(){
+                    closed_bars.push((*tf, *bar));
+                }
+            }
+        }
+        (eval_tf.is_none() || eval_closed, closed_bars)
+    }
+
+    pub fn update_exec_params(&mut self, param: ExecParam){
+        use ExecParam::*;
+        match param{
+            Margin(m) => self.exec_params.margin = m,
+            Lev(l) => self.exec_params.lev = l,
+            Tf(t) => self.exec_params.tf = t,
+        }
+    }
+
+    //simulated time for cooldowns and signal timestamps, replays follow the recorded receive times
+    pub fn set_clock(&mut self, time: u64){
+        self.cooldown.set_clock(time);
+    }
+
+    pub fn on_trade_closed(&mut self, trade: &TradeInfo){
+        self.cooldown.on_trade_closed(trade);
+    }
+
+    //synchronous UpdatePrice for backtests, `time` is the simulated clock
+    pub fn step(&mut self, price: Candle, time: u64) -> Option<TradeCommand>{
+        self.set_clock(time);
+        let (evaluate, _) = self.digest(price);
+
+        if let Some(pos) = &mut self.position{
+            pos.update_upnl(price.close);
+        }
+        if let Some(regime) = self.regime.update(price){
+            if let Strategy::Custom(strat) = &mut self.strategy{
+                strat.apply_regime(regime);
+            }
+        }
+        if !evaluate{
+            return None;
+        }
+
+        let values: Vec<Value> = self.get_indicators_data().into_iter().filter_map(|t| t.value).collect();
+        let trade = self.get_signal(price.close, values)?;
+        if self.gate(&trade).is_some(){
+            return None;
+        }
+        self.cooldown.on_signal_sent(&trade);
+        Some(trade)
+    }
+
+    fn get_votes(&self, price: f64, values: &[Value]) -> Option<Vec<Vote>>{
+        match &self.strategy{
+            Strategy::Ensemble(ens) => Some(ens.get_votes(values, price)),
+            _ => None,
         }
     }
 
@@ -166,79 +361,122 @@ impl SignalEngine{
 impl SignalEngine{
 
     pub async fn start(&mut self){
+        while let Some(cmd) = self.engine_rv.recv().await{
+            if !self.handle(cmd).await{
+                return;
+            }
+        }
+    }
 
-            let mut tick: u64 = 0;
-            
-            while let Some(cmd) = self.engine_rv.recv().await{
-           
-            match cmd {
-
-                EngineCommand::UpdatePrice(price) => {
-                    for (_tf, tracker) in &mut self.trackers{
-                            tracker.digest(price);
-                        }
+    //one command of the start loop, false once stopped. Replays drive the engine through it directly
+    pub async fn handle(&mut self, cmd: EngineCommand) -> bool{
+        match cmd {
 
-                    //self.display_indicators(price.close);
-                    let ind = self.get_indicators_data();
-                    let values: Vec<Value> = ind.iter().filter_map(|t| t.value).collect();
+            EngineCommand::UpdatePrice(price) => {
+                let (evaluate, closed_bars) = self.digest(price);
 
-                    if tick % 5 == 0{
-                        if let Some(sender) = &self.data_tx{
-                            sender.send(MarketCommand::UpdateIndicatorData(ind)).await;
-                        }
+                if !closed_bars.is_empty(){
+                    if let Some(sender) = &self.data_tx{
+                        let _ = sender.send(MarketCommand::StoreCandles(closed_bars)).await;
                     }
+                }
 
-                    if let Some(trade) = self.get_signal(price.close, values){
-                        let _ = self.trade_tx.try_send(trade);
+                //skipped bars are refetched by the market and come back as ReloadTracker
+                let gaps = self.take_gaps();
+                if let Some(sender) = &self.data_tx{
+                    for tf in gaps{
+                        let _ = sender.send(MarketCommand::ReloadTracker(tf)).await;
                     }
+                }
 
-                    tick += 1;
-                }, 
+                if let Some(pos) = &mut self.position{
+                    pos.update_upnl(price.close);
+                }
 
-                EngineCommand::UpdateStrategy(new_strat) =>{
-                    self.change_strategy(new_strat);
-                 },
+                if let Some(regime) = self.regime.update(price){
+                    self.on_regime_change(regime).await;
+                }
 
-                
-                EngineCommand::EditIndicators{indicators, price_data} =>{
-                    info!("Received Indicator Edit Vec of length : {}", indicators.len()); 
-                    
-
-                    for entry in indicators{
-                        match entry.edit{
-                            EditType::Add => { self.add_indicator(entry.id);},
-                            EditType::Remove => {self.remove_indicator(entry.id);},
-                            EditType::Toggle => {self.toggle_indicator(entry.id)},
-                        }
-                    }
-                    if let Some(data) = price_data{
-                        for (tf, prices) in data{
-                            self.load(tf, prices);
+                //self.display_indicators(price.close);
+                let ind = self.get_indicators_data();
+                let values: Vec<Value> = ind.iter().filter_map(|t| t.value).collect();
+
+                if self.tick % 5 == 0{
+                    if let Some(sender) = &self.data_tx{
+                        sender.send(MarketCommand::UpdateIndicatorData(ind)).await;
+                        if let Some(votes) = self.get_votes(price.close, &values){
+                            let _ = sender.send(MarketCommand::UpdateVotes(votes)).await;
                         }
                     }
-                   
                 }
-                
-                EngineCommand::UpdateExecParams(param)=>{
-                    use ExecParam::*;
-                    match param{
-                            Margin(m)=>{
-                                self.exec_params.margin = m;
-                        },
-                            Lev(l) => {
-                                self.exec_params.lev = l;
-                        },
-                            Tf(t) => {
-                                self.exec_params.tf = t;                                
+
+                if evaluate{
+                    match self.get_signal(price.close, values){
+                        Some(trade) => {
+                            let status = self.dispatch(trade);
+                            self.publish_signal(price.close, price.open_time, trade, status).await;
                         },
+                        None => self.last_event = None,
                     }
-                },
+                }
 
-                EngineCommand::Stop =>{ 
-                    return;
-                },
+                self.tick += 1;
+            }, 
+
+            EngineCommand::UpdateStrategy(new_strat) =>{
+                self.change_strategy(new_strat);
+             },
+
+            
+            EngineCommand::EditIndicators{indicators, price_data} =>{
+                info!("Received Indicator Edit Vec of length : {}", indicators.len()); 
+                
+
+                for entry in indicators{
+                    match entry.edit{
+                        EditType::Add => { self.add_indicator(entry.id);},
+                        EditType::Remove => {self.remove_indicator(entry.id);},
+                        EditType::Toggle => {self.toggle_indicator(entry.id)},
+                    }
+                }
+                if let Some(data) = price_data{
+                    for (tf, prices) in data{
+                        self.load(tf, prices).await;
+                    }
+                }
+               
             }
+            
+            EngineCommand::ReloadTracker{tf, price_data} =>{
+                self.reload(tf, price_data).await;
+            },
+
+            EngineCommand::UpdateExecParams(param)=>{
+                self.update_exec_params(param);
+            },
+
+            EngineCommand::ReceiveTrade(trade_info) =>{
+                self.on_trade_closed(&trade_info);
+            },
+
+            EngineCommand::UpdateSignalFilter(filter) =>{
+                self.update_signal_filter(filter);
+            },
+
+            EngineCommand::UpdatePosition(position) =>{
+                self.update_position(position);
+            },
+
+            EngineCommand::PauseEntries(paused) =>{
+                self.entries_paused = paused;
+                info!("Entries {}", if paused { "paused: stale feed" } else { "resumed" });
+            },
+
+            EngineCommand::Stop =>{ 
+                return false;
+            },
         }
+        true
     }
 
     pub fn display_indicators(&mut self, price: f64){
@@ -268,6 +506,10 @@ impl SignalEngine{
             }}
    
 
+        if let Some(tf) = trade_params.signal_filter.eval_on_close{
+            trackers.entry(tf).or_insert_with(|| Box::new(Tracker::new(tf)));
+        }
+
         //channels won't be used in backtesting, these are placeholders
         let (_tx, dummy_rv) = unbounded_channel::<EngineCommand>();
         let (dummy_tx, _rx) = bounded::<TradeCommand>(0);
@@ -279,6 +521,12 @@ impl SignalEngine{
             trackers,
             strategy: trade_params.strategy,
             exec_params: ExecParams{margin, lev: trade_params.lev, tf: trade_params.time_frame},
+            position: None,
+            regime: RegimeDetector::new(trade_params.time_frame, RegimeConfig::default()),
+            cooldown: Cooldown::new(trade_params.signal_filter),
+            last_event: None,
+            entries_paused: false,
+            tick: 0,
         }           
     }
 }
@@ -288,10 +536,15 @@ impl SignalEngine{
 
 pub enum EngineCommand{
 
-    UpdatePrice(Price),
+    UpdatePrice(Candle),
     UpdateStrategy(Strategy),
     EditIndicators{indicators: Vec<Entry>,price_data: Option<TimeFrameData>},
+    ReloadTracker{tf: TimeFrame, price_data: Vec<Candle>},
     UpdateExecParams(ExecParam),
+    UpdatePosition(Option<PositionSnapshot>),
+    ReceiveTrade(TradeInfo),
+    UpdateSignalFilter(SignalFilter),
+    PauseEntries(bool),
     Stop,
 }
 
@@ -6,14 +6,16 @@ use std::hash::BuildHasherDefault;
 use rustc_hash::FxHasher;
 
 use arraydeque::{ArrayDeque, behavior::Wrapping};
-use kwant::indicators::{Rsi, Atr, StochasticRsi, Price, Indicator, Ema, EmaCross, Sma, SmaRsi, Adx, Value};
+use kwant::indicators::{Rsi, Atr, StochasticRsi, Ema, EmaCross, Sma, Adx};
 
-use crate::trade_setup::TimeFrame;
-use crate::helper::get_time_now;
+use super::indicators::{Indicator, Kwant, Value, Factor, Macd, BollingerBands, KeltnerChannels, DonchianChannels, Vwap, Obv, VolumeMa, Supertrend, Psar, Ichimoku, Pipeline, Source, Transform};
+
+use crate::trade_setup::{TimeFrame, TradeCommand};
 use crate::MAX_HISTORY;
-use crate::IndicatorData;
+use crate::{IndicatorData, Candle, CandleAggregator};
 
 use serde::{Deserialize, Serialize};
+use log::warn;
 
 #[derive(Debug, Copy, Clone)]
 pub struct ExecParams{
@@ -50,6 +52,19 @@ pub enum IndicatorKind{
     Ema(u32),
     EmaCross{short:u32, long:u32},
     Sma(u32),
+    Macd{fast: u32, slow: u32, signal: u32},
+    Bollinger{periods: u32, std_dev: Factor},
+    Keltner{periods: u32, atr_periods: u32, multiplier: Factor},
+    Donchian(u32),
+    Vwap,
+    AnchoredVwap(u64),
+    Obv,
+    VolumeSma(u32),
+    VolumeEma(u32),
+    Supertrend{atr_periods: u32, multiplier: Factor},
+    Psar{step: Factor, max: Factor},
+    Ichimoku{tenkan: u32, kijun: u32, senkou: u32},
+    Pipeline{source: Source, transform: Transform},
 }
 
 #[derive(Debug)]
@@ -72,7 +87,7 @@ impl Handler{
         self.is_active
     }
 
-    pub fn update(&mut self,price: Price, after_close: bool){
+    pub fn update(&mut self,price: Candle, after_close: bool){
         if !after_close{
             self.indicator.update_before_close(price);
         }else{
@@ -83,8 +98,8 @@ impl Handler{
         self.indicator.get_last()
     }
 
-    pub fn load<'a,I: IntoIterator<Item=&'a Price>>(&mut self, price_data: I){
-        let data_vec: Vec<Price> = price_data.into_iter().copied().collect();
+    pub fn load<'a,I: IntoIterator<Item=&'a Candle>>(&mut self, price_data: I){
+        let data_vec: Vec<Candle> = price_data.into_iter().copied().collect();
         self.indicator.load(&data_vec);
     }
 
@@ -103,41 +118,85 @@ pub type IndexId = (IndicatorKind, TimeFrame);
 fn match_kind(kind: IndicatorKind) -> Box<dyn Indicator> {
     match kind {
         IndicatorKind::Rsi (periods) => {
-            Box::new(Rsi::new(periods, periods, None,None, None))
+            Box::new(Kwant(Box::new(Rsi::new(periods, periods, None,None, None))))
         }
          IndicatorKind::SmaOnRsi{periods, smoothing_length} => {
-            Box::new(SmaRsi::new(periods, smoothing_length))
+            let source = Source::Rsi(periods);
+            Box::new(Pipeline::new(source, source.to_kind().map(match_kind), Transform::Sma(smoothing_length)))
         }
         IndicatorKind::StochRsi{periods, k_smoothing, d_smoothing}=> {
-            Box::new(StochasticRsi::new(periods, k_smoothing, d_smoothing))
+            Box::new(Kwant(Box::new(StochasticRsi::new(periods, k_smoothing, d_smoothing))))
         }
         IndicatorKind::Adx { periods, di_length } => {
-            Box::new(Adx::new(periods, di_length))
+            Box::new(Kwant(Box::new(Adx::new(periods, di_length))))
         }
         IndicatorKind::Atr(periods) => {
-            Box::new(Atr::new(periods))
+            Box::new(Kwant(Box::new(Atr::new(periods))))
         }
         IndicatorKind::Ema(periods) => {
-            Box::new(Ema::new(periods))
+            Box::new(Kwant(Box::new(Ema::new(periods))))
         }
         IndicatorKind::EmaCross { short, long } => {
-            Box::new(EmaCross::new(short, long))
+            Box::new(Kwant(Box::new(EmaCross::new(short, long))))
         }
         IndicatorKind::Sma(periods) => {
-            Box::new(Sma::new(periods))
+            Box::new(Kwant(Box::new(Sma::new(periods))))
+        }
+        IndicatorKind::Macd { fast, slow, signal } => {
+            Box::new(Macd::new(fast, slow, signal))
+        }
+        IndicatorKind::Bollinger { periods, std_dev } => {
+            Box::new(BollingerBands::new(periods, std_dev.get()))
+        }
+        IndicatorKind::Keltner { periods, atr_periods, multiplier } => {
+            Box::new(KeltnerChannels::new(periods, atr_periods, multiplier.get()))
+        }
+        IndicatorKind::Donchian(periods) => {
+            Box::new(DonchianChannels::new(periods))
+        }
+        IndicatorKind::Vwap => {
+            Box::new(Vwap::session())
+        }
+        IndicatorKind::AnchoredVwap(anchor) => {
+            Box::new(Vwap::anchored(anchor))
+        }
+        IndicatorKind::Obv => {
+            Box::new(Obv::new())
+        }
+        IndicatorKind::VolumeSma(periods) => {
+            Box::new(VolumeMa::sma(periods))
+        }
+        IndicatorKind::VolumeEma(periods) => {
+            Box::new(VolumeMa::ema(periods))
+        }
+        IndicatorKind::Supertrend { atr_periods, multiplier } => {
+            Box::new(Supertrend::new(atr_periods, multiplier.get()))
+        }
+        IndicatorKind::Psar { step, max } => {
+            Box::new(Psar::new(step.get(), max.get()))
+        }
+        IndicatorKind::Ichimoku { tenkan, kijun, senkou } => {
+            Box::new(Ichimoku::new(tenkan, kijun, senkou))
+        }
+        IndicatorKind::Pipeline { source, transform } => {
+            Box::new(Pipeline::new(source, source.to_kind().map(match_kind), transform))
         }
     }
 }
 
 
-type History = Box<ArrayDeque<Price, MAX_HISTORY, Wrapping>>;
+type History = Box<ArrayDeque<Candle, MAX_HISTORY, Wrapping>>;
 
 #[derive(Debug)]
 pub struct Tracker{
     pub price_data: History,
     pub indicators: HashMap<IndicatorKind, Handler, BuildHasherDefault<FxHasher>>,
     tf: TimeFrame,
-    next_close: u64,
+    aggregator: CandleAggregator,
+    //latest update of the bar still open
+    current: Option<Candle>,
+    //(last bar before, first bar after) bars went missing, until the tracker is reloaded
+    gap: Option<(u64, u64)>,
 }
 
 
@@ -148,42 +207,72 @@ impl Tracker{
             price_data: Box::new(ArrayDeque::new()),
             indicators: HashMap::default(),
             tf,
-            next_close: Self::calc_next_close(tf),
+            aggregator: CandleAggregator::new(tf),
+            current: None,
+            gap: None,
         }
     }
 
 
-    pub fn digest(&mut self, price: Price){
-        let time = get_time_now(); 
-       
-        if time >= self.next_close{
-            self.next_close = Self::calc_next_close(self.tf);
-            self.price_data.push_back(price);
-            self.update_indicators(price, true);
-        }else{
-            self.update_indicators(price, false);
-        }
-        
+    //takes base (1m) candles and returns true if the price closed a bar
+    //a bar closes when a candle of a later bar arrives, its last update is what goes into history
+    pub fn digest(&mut self, base: Candle) -> bool{
+        let price = self.aggregator.update(base);
+        let bar = self.tf.bar_open(price.open_time);
+
+        let closed = match self.current{
+            Some(prev) => {
+                let prev_bar = self.tf.bar_open(prev.open_time);
+                if bar < prev_bar{
+                    //late message from a bar already closed
+                    return false;
+                }
+                if bar > prev_bar{
+                    if bar > self.tf.next_open(prev_bar){
+                        warn!("{} tracker skipped {} bar(s), reload needed", self.tf.as_str(), (bar - prev_bar) / self.tf.to_millis() - 1);
+                        self.gap = Some((prev_bar, bar));
+                    }
+                    self.price_data.push_back(prev);
+                    self.update_indicators(prev, true);
+                    true
+                }else{
+                    false
+                }
+            },
+            None => false,
+        };
+
+        self.current = Some(price);
+        self.update_indicators(price, false);
+        closed
+    }
+
+    //missing bars seen since the last call, the series has a hole until the tracker is reset and reloaded
+    pub fn take_gap(&mut self) -> Option<(u64, u64)>{
+        self.gap.take()
+    }
+
+    pub fn get_value(&self, kind: &IndicatorKind) -> Option<Value>{
+        self.indicators.get(kind).and_then(|handler| handler.get_value())
     }
 
-    fn update_indicators(&mut self,price: Price, after_close: bool){
+    fn update_indicators(&mut self,price: Candle, after_close: bool){
 
         for (_kind, handler) in &mut self.indicators{
             handler.update(price, after_close);
         }
     }
     
-    fn calc_next_close(tf: TimeFrame)-> u64 {
-        let now = get_time_now();
+    //`now` is when the snapshot was taken, the engine's clock
+    pub async fn load<I: IntoIterator<Item=Candle>>(&mut self, price_data: I, now: u64){
+        let mut buffer: Vec<Candle> = price_data.into_iter().collect();
 
-        let tf_ms = tf.to_millis();
-        ((now / tf_ms) + 1) * tf_ms
-    }
-    
-    
-    pub async fn load<I: IntoIterator<Item=Price>>(&mut self, price_data: I){
-        let buffer: Vec<Price> = price_data.into_iter().collect();
-        let safe_buff: Arc<[Price]> = buffer.clone().into();
+        //the last snapshot candle is usually the bar still forming, keep it open
+        let open = match buffer.last(){
+            Some(last) if self.tf.next_open(last.open_time) > now => buffer.pop(),
+            _ => None,
+        };
+        let safe_buff: Arc<[Candle]> = buffer.clone().into();
 
         let mut handles: Vec<tokio::task::JoinHandle<(IndicatorKind, Handler)>> = Vec::new();
         let mut temp_handlers = std::mem::take(&mut self.indicators);
@@ -207,6 +296,12 @@ impl Tracker{
         
         self.indicators = new_indicators;
         self.price_data.extend(buffer);
+
+        if let Some(candle) = open{
+            self.aggregator.seed(candle, now);
+            self.current = Some(candle);
+            self.update_indicators(candle, false);
+        }
     }
 
 
@@ -256,6 +351,9 @@ impl Tracker{
   
     pub fn reset(&mut self){
         self.price_data.clear();
+        self.current = None;
+        self.gap = None;
+        self.aggregator.reset();
         for (_kind, handler) in &mut self.indicators{
             handler.reset();
         }
@@ -265,7 +363,7 @@ impl Tracker{
 
 
 
-pub type TimeFrameData = HashMap<TimeFrame, Vec<Price>>;
+pub type TimeFrameData = HashMap<TimeFrame, Vec<Candle>>;
 
 #[derive(Copy, Clone, Debug,PartialEq, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -284,7 +382,49 @@ pub enum EditType{
 
 
 
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SkipReason{
+    Cooldown,
+    PositionOpen,
+    ExecutorBusy,
+    FeedStale,
+}
 
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SignalStatus{
+    Accepted,
+    Skipped(SkipReason),
+}
 
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SignalEvent{
+    pub timestamp: u64,
+    pub strategy: String,
+    pub indicators: Vec<IndicatorData>,
+    pub price: f64,
+    pub command: TradeCommand,
+    pub status: SignalStatus,
+}
 
+impl SignalEvent{
+    pub fn new(timestamp: u64, strategy: &str, indicators: Vec<IndicatorData>, price: f64, command: TradeCommand, status: SignalStatus) -> Self{
+        Self{
+            timestamp,
+            strategy: strategy.to_string(),
+            indicators,
+            price,
+            command,
+            status,
+        }
+    }
+}
 
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MarketSignalEvent{
+    pub asset: String,
+    pub event: SignalEvent,
+}
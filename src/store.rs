@@ -0,0 +1,164 @@
+use std::path::{Path, PathBuf};
+use std::collections::BTreeMap;
+use std::io::SeekFrom;
+
+use log::{info, warn};
+use tokio::fs::{self, File, OpenOptions};
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+
+use hyperliquid_rust_sdk::{Error, InfoClient};
+
+use crate::{Candle, TimeFrame};
+use crate::helper::{candles_snapshot, load_candles, get_time_now};
+
+
+pub const STORE_DIR: &str = "data/candles";
+
+//a file past this size is rewritten with its last MAX_STORED bars, deduplicated
+const COMPACT_BYTES: u64 = 32 * 1024 * 1024;
+const MAX_STORED: usize = 100_000;
+//enough for the last line of a file
+const TAIL_BYTES: u64 = 4096;
+
+
+//Closed bars on disk, one json line per candle in <root>/<asset>/<tf>.jsonl
+#[derive(Debug, Clone)]
+pub struct CandleStore{
+    root: PathBuf,
+}
+
+
+impl CandleStore{
+
+    pub fn new<P: Into<PathBuf>>(root: P) -> Self{
+        Self{ root: root.into() }
+    }
+
+    fn path(&self, asset: &str, tf: TimeFrame) -> PathBuf{
+        self.root.join(asset).join(format!("{}.jsonl", tf.as_str()))
+    }
+
+    //last `limit` candles in order, duplicates keep the latest write
+    pub async fn read(&self, asset: &str, tf: TimeFrame, limit: usize) -> std::io::Result<Vec<Candle>>{
+        let content = match fs::read_to_string(self.path(asset, tf)).await{
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+
+        //parsed from the end, only as far back as `limit` bars
+        let mut bars: BTreeMap<u64, Candle> = BTreeMap::new();
+        for line in content.lines().rev(){
+            if bars.len() >= limit{
+                break;
+            }
+            match serde_json::from_str::<Candle>(line){
+                Ok(candle) => {bars.entry(candle.open_time).or_insert(candle);},
+                //a torn last line after a crash is skipped
+                Err(e) => warn!("Skipping bad candle line in {} {}: {}", asset, tf.as_str(), e),
+            }
+        }
+        Ok(bars.into_values().collect())
+    }
+
+    //only bars newer than the last one on disk are written
+    pub async fn append(&self, asset: &str, tf: TimeFrame, candles: &[Candle]) -> std::io::Result<()>{
+        let path = self.path(asset, tf);
+        let last = last_open_time(&path).await?;
+        let mut buf = String::new();
+        let mut newest = last;
+        for candle in candles{
+            if newest.is_some_and(|t| candle.open_time <= t){
+                continue;
+            }
+            newest = Some(candle.open_time);
+            buf.push_str(&serde_json::to_string(candle)?);
+            buf.push('\n');
+        }
+        if buf.is_empty(){
+            return Ok(());
+        }
+        if let Some(dir) = path.parent(){
+            fs::create_dir_all(dir).await?;
+        }
+
+        let mut file = OpenOptions::new().create(true).append(true).open(&path).await?;
+        file.write_all(buf.as_bytes()).await?;
+        if file.metadata().await?.len() > COMPACT_BYTES{
+            drop(file);
+            self.compact(asset, tf).await?;
+        }
+        Ok(())
+    }
+
+    //rewrites the file sorted, deduplicated and cut to the last MAX_STORED bars
+    pub async fn compact(&self, asset: &str, tf: TimeFrame) -> std::io::Result<()>{
+        let path = self.path(asset, tf);
+        let bars = self.read(asset, tf, MAX_STORED).await?;
+        let mut buf = String::with_capacity(bars.len() * 128);
+        for candle in &bars{
+            buf.push_str(&serde_json::to_string(candle)?);
+            buf.push('\n');
+        }
+        let tmp = path.with_extension("jsonl.tmp");
+        fs::write(&tmp, buf).await?;
+        fs::rename(&tmp, &path).await?;
+        info!("Compacted {} {} candle store to {} bars", asset, tf.as_str(), bars.len());
+        Ok(())
+    }
+
+    //`candle_count` bars for warm-up: what is on disk plus the tail fetched from the exchange
+    pub async fn warm_up(&self, info_client: &InfoClient, asset: &str, tf: TimeFrame, candle_count: u64) -> Result<Vec<Candle>, Error>{
+        let stored = self.read(asset, tf, candle_count as usize).await.unwrap_or_else(|e| {
+            warn!("Failed to read candle store for {} {}: {}", asset, tf.as_str(), e);
+            Vec::new()
+        });
+
+        let tf_ms = tf.to_millis();
+        let now = get_time_now();
+        let from = match stored.last(){
+            Some(last) if now.saturating_sub(last.open_time) / tf_ms < candle_count => tf.next_open(last.open_time),
+            _ => {
+                let candles = load_candles(info_client, asset, tf, candle_count).await?;
+                self.store_closed(asset, tf, &candles, now).await;
+                return Ok(candles);
+            },
+        };
+
+        let tail = candles_snapshot(info_client, asset, tf, from, now).await?;
+        info!("{} {}: {} candles from disk, {} fetched", asset, tf.as_str(), stored.len(), tail.len());
+        self.store_closed(asset, tf, &tail, now).await;
+
+        let mut candles = stored;
+        candles.extend(tail.into_iter().filter(|c| c.open_time >= from));
+        let skip = candles.len().saturating_sub(candle_count as usize + 1);
+        Ok(candles.split_off(skip))
+    }
+
+    //the bar still forming is left out, it gets written once the stream closes it
+    async fn store_closed(&self, asset: &str, tf: TimeFrame, candles: &[Candle], now: u64){
+        let closed: Vec<Candle> = candles.iter()
+            .filter(|c| tf.next_open(c.open_time) <= now)
+            .copied()
+            .collect();
+        if let Err(e) = self.append(asset, tf, &closed).await{
+            warn!("Failed to write candle store for {} {}: {}", asset, tf.as_str(), e);
+        }
+    }
+}
+
+
+//open time of the last complete line, read from the end of the file
+async fn last_open_time(path: &Path) -> std::io::Result<Option<u64>>{
+    let mut file = match File::open(path).await{
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    let len = file.metadata().await?.len();
+    file.seek(SeekFrom::Start(len.saturating_sub(TAIL_BYTES))).await?;
+    let mut tail = String::new();
+    file.read_to_string(&mut tail).await?;
+
+    Ok(tail.lines().rev().find_map(|line| serde_json::from_str::<Candle>(line).ok()).map(|c| c.open_time))
+}
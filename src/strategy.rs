@@ -1,8 +1,8 @@
-use crate::TradeCommand;
+use crate::{TradeCommand, PositionSnapshot};
 //use crate::signal::IndicatorKind;
-use kwant::indicators::Value;
+use crate::signal::{Value, Factor, Source, Transform};
 use serde::{Deserialize, Serialize};
-use crate::signal::ExecParams;
+use crate::signal::{ExecParams, Regime};
 
 #[derive(Clone, Debug, Copy, PartialEq, Deserialize, Serialize)]
 #[serde(rename_all = "PascalCase")]
@@ -28,10 +28,20 @@ pub enum Stance{
 }
 
 
-#[derive(Clone, Debug, Copy, PartialEq,Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq,Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub enum Strategy{
     Custom(CustomStrategy),
+    Ensemble(EnsembleStrategy),
+}
+
+impl Strategy{
+    pub fn name(&self) -> &'static str{
+        match self{
+            Strategy::Custom(_) => "custom",
+            Strategy::Ensemble(_) => "ensemble",
+        }
+    }
 }
 
 #[derive(Clone, Debug, Copy, PartialEq, Serialize, Deserialize)]
@@ -41,6 +51,35 @@ pub struct CustomStrategy {
    pub style: Style,    
    pub stance: Stance,
    pub follow_trend: bool,
+   #[serde(default)]
+   pub regimes: Option<RegimeParams>,
+}
+
+#[derive(Clone, Debug, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RegimeSetting{
+    pub risk: Risk,
+    pub style: Style,
+    pub stance: Stance,
+}
+
+//Strategy parameters to switch to when the market enters each regime
+#[derive(Clone, Debug, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RegimeParams{
+    pub trending: RegimeSetting,
+    pub ranging: RegimeSetting,
+    pub volatile: RegimeSetting,
+}
+
+impl RegimeParams{
+    pub fn get(&self, regime: Regime) -> RegimeSetting{
+        match regime{
+            Regime::Trending => self.trending,
+            Regime::Ranging => self.ranging,
+            Regime::Volatile => self.volatile,
+        }
+    }
 }
 
 pub struct RsiRange{
@@ -62,7 +101,7 @@ pub struct StochRange{
 impl CustomStrategy{
 
     pub fn new(risk: Risk, style: Style, stance: Stance, follow_trend: bool) -> Self{
-        Self { risk, style, stance, follow_trend }
+        Self { risk, style, stance, follow_trend, regimes: None }
     }
 
     
@@ -109,8 +148,26 @@ impl CustomStrategy{
         self.follow_trend = follow_trend;
     }
 
+    pub fn update_regimes(&mut self, regimes: Option<RegimeParams>){
+        self.regimes = regimes;
+    }
+
+    //returns true if the strategy changed
+    pub fn apply_regime(&mut self, regime: Regime) -> bool{
+        let setting = match self.regimes{
+            Some(params) => params.get(regime),
+            None => return false,
+        };
+
+        let changed = self.risk != setting.risk || self.style != setting.style || self.stance != setting.stance;
+        self.update_risk(setting.risk);
+        self.update_style(setting.style);
+        self.update_direction(setting.stance);
+        changed
+    }
+
 
-    pub fn generate_signal(&self, data: Vec<Value>, price: f64, params: ExecParams) -> Option<TradeCommand> {
+    pub fn generate_signal(&self, data: Vec<Value>, price: f64, params: ExecParams, position: Option<&PositionSnapshot>) -> Option<TradeCommand> {
     // Extract indicator values from the data
     let mut rsi_value = None;
     let mut srsi_value = None;
@@ -118,17 +175,22 @@ impl CustomStrategy{
     let mut ema_cross = None;
     let mut adx_value = None;
     let mut atr_value = None;
+    let mut volume_ma = None;
+    let trends: Vec<bool> = data.iter().filter_map(Value::trend).collect();
     
     for value in data {
+        if let Some(srsi) = value.smoothed_rsi(){
+            srsi_value = Some(srsi);
+        }
         match value {
             Value::RsiValue(rsi) => rsi_value = Some(rsi),
-            Value::SmaRsiValue(srsi) => srsi_value = Some(srsi),
             Value::StochRsiValue { k, d } => stoch_rsi = Some((k, d)),
             Value::EmaCrossValue { short, long, trend } => {
                 ema_cross = Some((short, long, trend))
             }
             Value::AdxValue(adx) => adx_value = Some(adx),
             Value::AtrValue(atr) => atr_value = Some(atr),
+            Value::VolumeMaValue { volume, average } => volume_ma = Some((volume, average)),
             _ => {} // Handle other indicators as needed
         }
     }
@@ -138,7 +200,10 @@ impl CustomStrategy{
         if let Some(srsi) = srsi_value{
                 if let Some(stoch) = stoch_rsi{
                     let max_size = (params.margin * params.lev as f64) / price;
-                    return self.rsi_based_scalp(rsi, srsi, stoch, max_size);
+                    let signal = self.rsi_based_scalp(rsi, srsi, stoch, max_size);
+                    let signal = confirm_volume(signal, volume_ma);
+                    let signal = if self.follow_trend {with_trend(signal, &trends)} else {signal};
+                    return manage_position(signal, position);
                 }
             }
     }
@@ -344,19 +409,264 @@ impl Default for CustomStrategy{
             style: Style::Scalp,
             stance: Stance::Neutral,
             follow_trend: true,
+            regimes: None,
     }
 }
 }
 
 
 
+//Decide what to do with an entry signal given the position currently held:
+//no position -> enter, same side -> hold, opposite side -> exit
+fn manage_position(signal: Option<TradeCommand>, position: Option<&PositionSnapshot>) -> Option<TradeCommand>{
+    let pos = match position{
+        Some(pos) => pos,
+        None => return signal,
+    };
+
+    match signal{
+        Some(TradeCommand::ExecuteTrade{is_long, ..}) | Some(TradeCommand::OpenTrade{is_long, ..}) if is_long != pos.is_long => {
+            Some(TradeCommand::CloseTrade{size: pos.size})
+        },
+        _ => None,
+    }
+}
+
+
+
+//With a volume MA active, entries need the last closed bar's volume to be above its average
+fn confirm_volume(signal: Option<TradeCommand>, volume_ma: Option<(f64, f64)>) -> Option<TradeCommand>{
+    match (signal, volume_ma){
+        (Some(cmd), Some((volume, average))) if cmd.entry_side().is_some() && volume < average => None,
+        (signal, _) => signal,
+    }
+}
+
+
+
+//Entries against the direction of any active trend indicator (Supertrend, PSAR, Ichimoku) are dropped
+fn with_trend(signal: Option<TradeCommand>, trends: &[bool]) -> Option<TradeCommand>{
+    match signal{
+        Some(cmd) => match cmd.entry_side(){
+            Some(is_long) if trends.iter().any(|&up| up != is_long) => None,
+            _ => Some(cmd),
+        },
+        None => None,
+    }
+}
+
+
+
+//ENSEMBLE
+#[derive(Clone, Debug, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Voter{
+    RsiScalp,
+    EmaTrend,
+    AdxBreakout,
+    //any pipeline against two bands, the pipeline has to be active on the market
+    Pipeline{source: Source, transform: Transform, low: Factor, high: Factor},
+}
+
+#[derive(Clone, Debug, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WeightedVoter{
+    pub voter: Voter,
+    pub weight: f64,
+}
+
+//score is in [-1, 1], negative => short, positive => long, None => missing indicators
+#[derive(Clone, Debug, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Vote{
+    pub voter: Voter,
+    pub weight: f64,
+    pub score: Option<f64>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EnsembleStrategy{
+    pub risk: Risk,
+    pub style: Style,
+    pub stance: Stance,
+    pub voters: Vec<WeightedVoter>,
+    pub threshold: f64,
+}
+
+
+#[derive(Default)]
+struct VoterInputs{
+    rsi: Option<f64>,
+    srsi: Option<f64>,
+    stoch_rsi: Option<(f64, f64)>,
+    ema_cross: Option<(f64, f64, bool)>,
+    ema: Option<f64>,
+    adx: Option<f64>,
+    pipelines: Vec<(Source, Transform, f64)>,
+}
+
+impl VoterInputs{
+    fn new(data: &[Value]) -> Self{
+        let mut inputs = VoterInputs::default();
+        for value in data{
+            if let Some(srsi) = value.smoothed_rsi(){
+                inputs.srsi = Some(srsi);
+            }
+            match *value{
+                Value::RsiValue(rsi) => inputs.rsi = Some(rsi),
+                Value::PipelineValue{source, transform, value} => inputs.pipelines.push((source, transform, value)),
+                Value::StochRsiValue{k, d} => inputs.stoch_rsi = Some((k, d)),
+                Value::EmaCrossValue{short, long, trend} => inputs.ema_cross = Some((short, long, trend)),
+                Value::EmaValue(ema) => inputs.ema = Some(ema),
+                Value::AdxValue(adx) => inputs.adx = Some(adx),
+                _ => {},
+            }
+        }
+        inputs
+    }
+}
+
 
+impl EnsembleStrategy{
 
+    pub fn new(risk: Risk, style: Style, stance: Stance, voters: Vec<WeightedVoter>, threshold: f64) -> Self{
+        Self{ risk, style, stance, voters, threshold }
+    }
 
+    pub fn update_threshold(&mut self, threshold: f64){
+        self.threshold = threshold;
+    }
 
+    pub fn get_votes(&self, data: &[Value], price: f64) -> Vec<Vote>{
+        let inputs = VoterInputs::new(data);
+
+        self.voters.iter().map(|v|{
+            let score = match v.voter{
+                Voter::RsiScalp => Self::rsi_scalp_vote(&inputs),
+                Voter::EmaTrend => Self::ema_trend_vote(&inputs),
+                Voter::AdxBreakout => Self::adx_breakout_vote(&inputs, price),
+                Voter::Pipeline{source, transform, low, high} => Self::pipeline_vote(&inputs, source, transform, low.get(), high.get()),
+            };
+            Vote{voter: v.voter, weight: v.weight, score: score.map(|s| s.clamp(-1.0, 1.0))}
+        }).collect()
+    }
 
+    //weighted mean of the available scores
+    pub fn conviction(votes: &[Vote]) -> Option<f64>{
+        let (sum, total_weight) = votes.iter().fold((0.0, 0.0), |(sum, w), vote|{
+            match vote.score{
+                Some(score) => (sum + score * vote.weight, w + vote.weight.abs()),
+                None => (sum, w),
+            }
+        });
 
+        if total_weight <= 0.0{
+            return None;
+        }
+        Some(sum / total_weight)
+    }
 
+    pub fn generate_signal(&self, data: &[Value], price: f64, params: ExecParams, position: Option<&PositionSnapshot>) -> Option<TradeCommand>{
+        let votes = self.get_votes(data, price);
+        let conviction = Self::conviction(&votes)?;
 
+        if conviction.abs() < self.threshold{
+            return None;
+        }
 
+        let is_long = conviction > 0.0;
+        if (is_long && self.stance == Stance::Bear) || (!is_long && self.stance == Stance::Bull){
+            return None;
+        }
 
+        let max_size = (params.margin * params.lev as f64) / price;
+        let size_factor = match self.risk{
+            Risk::Low => 0.5,
+            Risk::Normal => 0.7,
+            Risk::High => 0.9,
+        };
+        let duration = match self.style{
+            Style::Scalp => 420,
+            Style::Swing => 4 * 60 * 60,
+        };
+
+        let signal = Some(TradeCommand::ExecuteTrade{
+            size: size_factor * conviction.abs().min(1.0) * max_size,
+            is_long,
+            duration,
+        });
+
+        manage_position(signal, position)
+    }
+
+
+    //mean reversion: oversold => long, overbought => short
+    fn rsi_scalp_vote(inputs: &VoterInputs) -> Option<f64>{
+        let rsi = inputs.rsi?;
+        let mut scores = vec![(50.0 - rsi) / 50.0];
+
+        if let Some(srsi) = inputs.srsi{
+            scores.push((50.0 - srsi) / 50.0);
+        }
+        if let Some((k, d)) = inputs.stoch_rsi{
+            scores.push((50.0 - (k + d) / 2.0) / 50.0);
+        }
+
+        Some(scores.iter().sum::<f64>() / scores.len() as f64)
+    }
+
+    //trend following: 1% spread between the EMAs is full conviction
+    fn ema_trend_vote(inputs: &VoterInputs) -> Option<f64>{
+        let (short, long, _trend) = inputs.ema_cross?;
+        if long == 0.0{
+            return None;
+        }
+        Some((short - long) / long * 100.0)
+    }
+
+    //strong ADX confirms a move away from the EMA
+    fn adx_breakout_vote(inputs: &VoterInputs, price: f64) -> Option<f64>{
+        const ADX_FLOOR: f64 = 20.0;
+        const ADX_FULL: f64 = 40.0;
+
+        let adx = inputs.adx?;
+        let direction = if let Some(ema) = inputs.ema{
+            if price >= ema {1.0} else {-1.0}
+        }else if let Some((_short, _long, trend)) = inputs.ema_cross{
+            if trend {1.0} else {-1.0}
+        }else{
+            return None;
+        };
+
+        let strength = ((adx - ADX_FLOOR) / (ADX_FULL - ADX_FLOOR)).clamp(0.0, 1.0);
+        Some(direction * strength)
+    }
+
+    //mean reversion on the pipeline's value: `low` or under => long, `high` or over => short, linear in between.
+    //A negative weight turns it into a trend vote
+    fn pipeline_vote(inputs: &VoterInputs, source: Source, transform: Transform, low: f64, high: f64) -> Option<f64>{
+        if high <= low{
+            return None;
+        }
+        let (_, _, value) = inputs.pipelines.iter().find(|p| p.0 == source && p.1 == transform)?;
+        Some(1.0 - 2.0 * (value - low) / (high - low))
+    }
+}
+
+
+impl Default for EnsembleStrategy{
+    fn default() -> Self{
+        Self{
+            risk: Risk::Normal,
+            style: Style::Scalp,
+            stance: Stance::Neutral,
+            voters: vec![
+                WeightedVoter{voter: Voter::RsiScalp, weight: 1.0},
+                WeightedVoter{voter: Voter::EmaTrend, weight: 1.0},
+                WeightedVoter{voter: Voter::AdxBreakout, weight: 1.0},
+            ],
+            threshold: 0.5,
+        }
+    }
+}
@@ -1,10 +1,15 @@
 use std::fmt;
 
+use chrono::{Datelike, Duration, NaiveDate};
+
 use log::info;
 use hyperliquid_rust_sdk::{ExchangeClient, ExchangeResponseStatus, Error, TradeInfo as HLTradeInfo};
 //use kwant::indicators::Price;
 
 use crate::strategy::{Strategy, CustomStrategy};
+use crate::helper::get_time_now;
+use crate::signal::{Regime, SignalFilter};
+use crate::feed::WatchdogConfig;
 use serde::{Deserialize, Serialize};
 
 
@@ -16,6 +21,10 @@ pub struct TradeParams {
     pub lev: u32,
     pub trade_time: u64,  
     pub time_frame: TimeFrame,
+    #[serde(default)]
+    pub signal_filter: SignalFilter,
+    #[serde(default)]
+    pub watchdog: WatchdogConfig,
 }
 
 
@@ -54,6 +63,8 @@ impl Default for TradeParams {
             lev: 20,
             trade_time: 300,
             time_frame: TimeFrame::Min5,
+            signal_filter: SignalFilter::default(),
+            watchdog: WatchdogConfig::default(),
         }
     }
 }
@@ -87,6 +98,19 @@ pub enum TradeCommand{
 }
 
 
+impl TradeCommand{
+    //side of the position the command opens, None if it doesn't open one
+    pub fn entry_side(&self) -> Option<bool>{
+        match *self{
+            TradeCommand::ExecuteTrade{is_long, ..} => Some(is_long),
+            TradeCommand::OpenTrade{is_long, ..} => Some(is_long),
+            TradeCommand::BuildPosition{is_long, ..} => Some(is_long),
+            _ => None,
+        }
+    }
+}
+
+
 #[derive(Clone, Debug, Copy, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct TradeInfo{
@@ -94,9 +118,14 @@ pub struct TradeInfo{
     pub close: f64,
     pub pnl: f64,
     pub fee: f64,
+    //funding received while the position was open (negative when paid), already in pnl
+    #[serde(default)]
+    pub funding: f64,
     pub is_long: bool,
     pub duration: Option<u64>,
     pub oid: (u64, u64),
+    #[serde(default)]
+    pub regime: Option<Regime>,
 }
 
 
@@ -120,6 +149,43 @@ pub struct TradeFillInfo{
     pub oid: u64,  
     pub is_long: bool, }
 
+#[derive(Clone, Debug, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PositionSnapshot{
+    pub is_long: bool,
+    pub size: f64,
+    pub entry: f64,
+    pub upnl: f64,
+    pub open_time: u64,
+}
+
+impl PositionSnapshot{
+
+    pub fn new(fill: &TradeFillInfo) -> Self{
+        Self{
+            is_long: fill.is_long,
+            size: fill.sz,
+            entry: fill.price,
+            upnl: 0.0,
+            open_time: get_time_now(),
+        }
+    }
+
+    pub fn update_upnl(&mut self, price: f64){
+        self.upnl = if self.is_long{
+            self.size * (price - self.entry)
+        }else{
+            self.size * (self.entry - price)
+        };
+    }
+
+    //milliseconds since the position was opened, `now` is the caller's clock (simulated in replays)
+    pub fn age(&self, now: u64) -> u64{
+        now.saturating_sub(self.open_time)
+    }
+}
+
+
 impl From<LiquidationFillInfo> for TradeFillInfo{
 
     fn from(liq: LiquidationFillInfo) -> Self{
@@ -228,6 +294,31 @@ impl TimeFrame{
         self.to_secs() * 1000
     }
 
+    //open time of the bar holding `time`, weeks start on Monday 00:00 UTC and months on the 1st like the exchange's candles
+    pub fn bar_open(&self, time: u64) -> u64{
+        const DAY_MS: u64 = 24 * 60 * 60 * 1000;
+        match self{
+            //the epoch fell on a Thursday
+            TimeFrame::Week => time - (time + 3 * DAY_MS) % (7 * DAY_MS),
+            TimeFrame::Month => {
+                let epoch = NaiveDate::from_ymd_opt(1970, 1, 1).unwrap();
+                let date = epoch + Duration::days((time / DAY_MS) as i64);
+                let first = date.with_day(1).unwrap();
+                first.signed_duration_since(epoch).num_days() as u64 * DAY_MS
+            },
+            _ => time - time % self.to_millis(),
+        }
+    }
+
+    //open time of the bar following the one open at `time`
+    pub fn next_open(&self, time: u64) -> u64{
+        match self{
+            //a month is 28 to 31 days, 32 days past its first always lands in the next one
+            TimeFrame::Month => self.bar_open(self.bar_open(time) + 32 * 24 * 60 * 60 * 1000),
+            _ => self.bar_open(time) + self.to_millis(),
+        }
+    }
+
 
 }
 